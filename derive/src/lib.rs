@@ -0,0 +1,187 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, LitStr};
+
+/// bind the fields of a struct to application settings.
+/// every field maps to `<prefix><field name>`. Fields marked with `user` can be overridden per user.
+/// the struct gets `Default` from the field defaults, `SettingsBinding` and an async `save_to`
+///
+/// ```ignore
+/// #[derive(SettingsBinding, Clone, Debug)]
+/// #[settings(prefix = "bot.")]
+/// pub struct BotSettings {
+///     #[setting(default = "!")]
+///     pub prefix: String,
+///     #[setting(default = 10, user)]
+///     pub limit: u32,
+/// }
+/// ```
+#[proc_macro_derive(SettingsBinding, attributes(settings, setting))]
+pub fn derive_settings_binding(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match settings_binding(input) {
+        Ok(output) => output.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// a field of the struct along with its `#[setting(...)]` options
+struct SettingField {
+    ident: syn::Ident,
+    ty: syn::Type,
+    default: Option<Expr>,
+    user: bool,
+}
+
+impl SettingField {
+    /// the default of the field as a value of the field type. String literals are converted with `From`
+    fn default_value(&self) -> TokenStream2 {
+        let ty = &self.ty;
+        match self.default.as_ref() {
+            Some(Expr::Lit(expr)) if matches!(expr.lit, Lit::Str(_)) => quote! {{
+                let value: #ty = ::std::convert::From::from(#expr);
+                value
+            }},
+            Some(expr) => quote! {{
+                let value: #ty = #expr;
+                value
+            }},
+            None => quote! { <#ty as ::std::default::Default>::default() },
+        }
+    }
+}
+
+fn settings_binding(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut prefix = String::new();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("settings")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                prefix = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unsupported settings option, expected prefix"))
+            }
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "SettingsBinding needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "SettingsBinding can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut settings = Vec::new();
+    for field in fields.iter() {
+        let mut setting = SettingField {
+            ident: field.ident.clone().expect("named fields have an ident"),
+            ty: field.ty.clone(),
+            default: None,
+            user: false,
+        };
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("setting")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    setting.default = Some(meta.value()?.parse::<Expr>()?);
+                    Ok(())
+                } else if meta.path.is_ident("user") {
+                    setting.user = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported setting option, expected default or user"))
+                }
+            })?;
+        }
+        settings.push(setting);
+    }
+
+    let idents = settings.iter().map(|setting| &setting.ident).collect::<Vec<_>>();
+    let types = settings.iter().map(|setting| &setting.ty).collect::<Vec<_>>();
+    let names = settings
+        .iter()
+        .map(|setting| format!("{}{}", prefix, setting.ident))
+        .collect::<Vec<_>>();
+    let users = settings.iter().map(|setting| setting.user).collect::<Vec<_>>();
+    let defaults = settings.iter().map(SettingField::default_value).collect::<Vec<_>>();
+
+    Ok(quote! {
+        impl #impl_generics ::std::default::Default for #name #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#idents: #defaults,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::levelcrush::app::settings::binding::SettingsBinding for #name #ty_generics #where_clause {
+            fn schemas() -> ::std::vec::Vec<::levelcrush::app::settings::schema::SettingSchema> {
+                use ::levelcrush::app::settings::binding::SettingValue;
+                let defaults = <Self as ::std::default::Default>::default();
+                ::std::vec![
+                    #(
+                        ::levelcrush::app::settings::schema::SettingSchema::new(
+                            #names,
+                            <#types as SettingValue>::value_type(),
+                        )
+                        .with_default(&defaults.#idents.to_setting()),
+                    )*
+                ]
+            }
+
+            fn load_from<Extension: Clone>(
+                settings: &::levelcrush::app::settings::ApplicationSettings<Extension>,
+                user: ::std::option::Option<&str>,
+            ) -> ::levelcrush::anyhow::Result<Self> {
+                use ::levelcrush::app::settings::binding::SettingValue;
+                Ok(Self {
+                    #(
+                        #idents: {
+                            let value = match user {
+                                Some(user) if #users => settings.get_user(user, #names),
+                                _ => settings.get_global(#names),
+                            };
+                            match value {
+                                Some(value) => <#types as SettingValue>::from_setting(&value)?,
+                                None => #defaults,
+                            }
+                        },
+                    )*
+                })
+            }
+
+            fn values(&self) -> ::std::vec::Vec<(&'static str, ::std::string::String, bool)> {
+                use ::levelcrush::app::settings::binding::SettingValue;
+                ::std::vec![
+                    #((#names, self.#idents.to_setting(), #users),)*
+                ]
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// write every field to the settings. See `ApplicationSettings::save_binding`
+            pub async fn save_to<Extension: Clone>(
+                &self,
+                settings: &mut ::levelcrush::app::settings::ApplicationSettings<Extension>,
+                user: ::std::option::Option<&str>,
+            ) -> ::levelcrush::anyhow::Result<()> {
+                settings.save_binding(self, user).await
+            }
+        }
+    })
+}
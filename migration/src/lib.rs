@@ -1,12 +1,32 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20240220_232237_start;
+mod m20261018_090000_application_keys;
+mod m20261018_100000_application_settings_schema;
+mod m20261018_110000_application_settings_history;
+mod m20261018_120000_application_scoped_settings;
+mod m20261018_130000_application_settings_secret;
+mod m20261018_140000_application_process_logs_structured;
+mod m20261018_150000_application_log_retention;
+mod m20261018_160000_application_process_runs;
+mod m20261018_170000_application_settings_updated;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20240220_232237_start::Migration)]
+        vec![
+            Box::new(m20240220_232237_start::Migration),
+            Box::new(m20261018_090000_application_keys::Migration),
+            Box::new(m20261018_100000_application_settings_schema::Migration),
+            Box::new(m20261018_110000_application_settings_history::Migration),
+            Box::new(m20261018_120000_application_scoped_settings::Migration),
+            Box::new(m20261018_130000_application_settings_secret::Migration),
+            Box::new(m20261018_140000_application_process_logs_structured::Migration),
+            Box::new(m20261018_150000_application_log_retention::Migration),
+            Box::new(m20261018_160000_application_process_runs::Migration),
+            Box::new(m20261018_170000_application_settings_updated::Migration),
+        ]
     }
 }
@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApplicationKeys::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApplicationKeys::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApplicationKeys::Application).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(ApplicationKeys::Hash)
+                            .char_len(32)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(ApplicationKeys::HashSecret).char_len(32).not_null())
+                    .col(ColumnDef::new(ApplicationKeys::Name).string_len(32).not_null())
+                    .col(ColumnDef::new(ApplicationKeys::Scopes).text().not_null())
+                    .col(ColumnDef::new(ApplicationKeys::ExpiresAt).big_integer().not_null())
+                    .col(ColumnDef::new(ApplicationKeys::LastUsedAt).big_integer().not_null())
+                    .col(ColumnDef::new(ApplicationKeys::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(ApplicationKeys::UpdatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(ApplicationKeys::DeletedAt).big_integer().not_null())
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-keys-app-name")
+                            .table(ApplicationKeys::Table)
+                            .col(ApplicationKeys::Application)
+                            .col(ApplicationKeys::Name),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-keys-app-hashsecret")
+                            .table(ApplicationKeys::Table)
+                            .col(ApplicationKeys::Application)
+                            .col(ApplicationKeys::HashSecret),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationKeys::Table, ApplicationKeys::Application)
+                            .to(Applications::Table, Applications::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApplicationKeys::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Applications {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationKeys {
+    Table,
+    Id,
+    Application,
+    Hash,
+    HashSecret,
+    Name,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
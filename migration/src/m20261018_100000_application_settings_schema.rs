@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApplicationSettings::Table)
+                    .add_column(
+                        ColumnDef::new(ApplicationSettings::ValueType)
+                            .string_len(16)
+                            .not_null()
+                            .default("string"),
+                    )
+                    .add_column(ColumnDef::new(ApplicationSettings::ValueOptions).text().null())
+                    .add_column(ColumnDef::new(ApplicationSettings::DefaultValue).text().null())
+                    .add_column(ColumnDef::new(ApplicationSettings::Description).text().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApplicationSettings::Table)
+                    .drop_column(ApplicationSettings::ValueType)
+                    .drop_column(ApplicationSettings::ValueOptions)
+                    .drop_column(ApplicationSettings::DefaultValue)
+                    .drop_column(ApplicationSettings::Description)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApplicationSettings {
+    Table,
+    ValueType,
+    ValueOptions,
+    DefaultValue,
+    Description,
+}
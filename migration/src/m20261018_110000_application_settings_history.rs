@@ -0,0 +1,119 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApplicationSettingsHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::Application)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::Setting)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::HashUser)
+                            .char_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::Scope)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ApplicationSettingsHistory::OldValue).text().null())
+                    .col(ColumnDef::new(ApplicationSettingsHistory::NewValue).text().null())
+                    .col(ColumnDef::new(ApplicationSettingsHistory::Actor).string().not_null())
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationSettingsHistory::DeletedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-settings-history-app-setting-user")
+                            .table(ApplicationSettingsHistory::Table)
+                            .col(ApplicationSettingsHistory::Application)
+                            .col(ApplicationSettingsHistory::Setting)
+                            .col(ApplicationSettingsHistory::HashUser),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(
+                                ApplicationSettingsHistory::Table,
+                                ApplicationSettingsHistory::Application,
+                            )
+                            .to(Applications::Table, Applications::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationSettingsHistory::Table, ApplicationSettingsHistory::Setting)
+                            .to(ApplicationSettings::Table, ApplicationSettings::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApplicationSettingsHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Applications {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationSettings {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationSettingsHistory {
+    Table,
+    Id,
+    Application,
+    Setting,
+    HashUser,
+    Scope,
+    OldValue,
+    NewValue,
+    Actor,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
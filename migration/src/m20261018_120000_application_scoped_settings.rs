@@ -0,0 +1,130 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApplicationScopedSettings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::Application)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::Hash)
+                            .char_len(32)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::ScopeType)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::ScopeId)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::Setting)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ApplicationScopedSettings::Value).text().not_null())
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationScopedSettings::DeletedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-scopedsettings-app-setting-scope")
+                            .table(ApplicationScopedSettings::Table)
+                            .col(ApplicationScopedSettings::Application)
+                            .col(ApplicationScopedSettings::Setting)
+                            .col(ApplicationScopedSettings::ScopeType)
+                            .col(ApplicationScopedSettings::ScopeId)
+                            .unique(),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-scopedsettings-app-scope")
+                            .table(ApplicationScopedSettings::Table)
+                            .col(ApplicationScopedSettings::Application)
+                            .col(ApplicationScopedSettings::ScopeType)
+                            .col(ApplicationScopedSettings::ScopeId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationScopedSettings::Table, ApplicationScopedSettings::Application)
+                            .to(Applications::Table, Applications::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationScopedSettings::Table, ApplicationScopedSettings::Setting)
+                            .to(ApplicationSettings::Table, ApplicationSettings::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApplicationScopedSettings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Applications {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationSettings {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationScopedSettings {
+    Table,
+    Id,
+    Application,
+    Hash,
+    ScopeType,
+    ScopeId,
+    Setting,
+    Value,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
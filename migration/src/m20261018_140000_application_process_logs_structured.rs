@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApplicationProcessLogs::Table)
+                    .add_column(ColumnDef::new(ApplicationProcessLogs::Fields).text().null())
+                    .add_column(
+                        ColumnDef::new(ApplicationProcessLogs::SubId)
+                            .string_len(255)
+                            .not_null()
+                            .default(""),
+                    )
+                    .add_column(
+                        ColumnDef::new(ApplicationProcessLogs::CorrelationId)
+                            .string_len(64)
+                            .not_null()
+                            .default(""),
+                    )
+                    .add_column(
+                        ColumnDef::new(ApplicationProcessLogs::Module)
+                            .string_len(255)
+                            .not_null()
+                            .default(""),
+                    )
+                    .add_column(
+                        ColumnDef::new(ApplicationProcessLogs::Line)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("app-processlog-app-correlation")
+                    .table(ApplicationProcessLogs::Table)
+                    .col(ApplicationProcessLogs::Application)
+                    .col(ApplicationProcessLogs::CorrelationId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("app-processlog-app-correlation")
+                    .table(ApplicationProcessLogs::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApplicationProcessLogs::Table)
+                    .drop_column(ApplicationProcessLogs::Fields)
+                    .drop_column(ApplicationProcessLogs::SubId)
+                    .drop_column(ApplicationProcessLogs::CorrelationId)
+                    .drop_column(ApplicationProcessLogs::Module)
+                    .drop_column(ApplicationProcessLogs::Line)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApplicationProcessLogs {
+    Table,
+    Application,
+    Fields,
+    SubId,
+    CorrelationId,
+    Module,
+    Line,
+}
@@ -0,0 +1,139 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApplicationLogRetention::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::Application)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::Hash)
+                            .char_len(32)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    // 0 applies the rule to every process of the application
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::Process)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    // null applies the rule to every level
+                    .col(ColumnDef::new(ApplicationLogRetention::Level).tiny_integer().null())
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::KeepSeconds)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::Archive)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationLogRetention::DeletedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-log-retention-app")
+                            .table(ApplicationLogRetention::Table)
+                            .col(ApplicationLogRetention::Application),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationLogRetention::Table, ApplicationLogRetention::Application)
+                            .to(Applications::Table, Applications::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // the purge walks expired rows of an application oldest first
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("app-processlog-app-created")
+                    .table(ApplicationProcessLogs::Table)
+                    .col(ApplicationProcessLogs::Application)
+                    .col(ApplicationProcessLogs::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("app-processlog-app-created")
+                    .table(ApplicationProcessLogs::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ApplicationLogRetention::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Applications {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationProcessLogs {
+    Table,
+    Application,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationLogRetention {
+    Table,
+    Id,
+    Application,
+    Hash,
+    Process,
+    Level,
+    KeepSeconds,
+    Archive,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
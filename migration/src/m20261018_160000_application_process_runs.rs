@@ -0,0 +1,133 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApplicationProcessRuns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::Application)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ApplicationProcessRuns::Process).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::Instance)
+                            .char_len(36)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(ApplicationProcessRuns::Host).string().not_null())
+                    .col(ColumnDef::new(ApplicationProcessRuns::Pid).big_integer().not_null())
+                    .col(ColumnDef::new(ApplicationProcessRuns::Status).string_len(16).not_null())
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::StartedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::HeartbeatAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::FinishedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::CreatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::UpdatedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ApplicationProcessRuns::DeletedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-processrun-process-started")
+                            .table(ApplicationProcessRuns::Table)
+                            .col(ApplicationProcessRuns::Process)
+                            .col(ApplicationProcessRuns::StartedAt),
+                    )
+                    .index(
+                        Index::create()
+                            .if_not_exists()
+                            .name("app-processrun-app-status-heartbeat")
+                            .table(ApplicationProcessRuns::Table)
+                            .col(ApplicationProcessRuns::Application)
+                            .col(ApplicationProcessRuns::Status)
+                            .col(ApplicationProcessRuns::HeartbeatAt),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationProcessRuns::Table, ApplicationProcessRuns::Application)
+                            .to(Applications::Table, Applications::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApplicationProcessRuns::Table, ApplicationProcessRuns::Process)
+                            .to(ApplicationProcesses::Table, ApplicationProcesses::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApplicationProcessRuns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Applications {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationProcesses {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationProcessRuns {
+    Table,
+    Id,
+    Application,
+    Process,
+    Instance,
+    Host,
+    Pid,
+    Status,
+    StartedAt,
+    HeartbeatAt,
+    FinishedAt,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
@@ -0,0 +1,92 @@
+use sea_orm_migration::prelude::*;
+
+/// every settings table with the name of its (application, updated_at) index
+fn indexes() -> [(DynIden, &'static str); 4] {
+    [
+        (ApplicationSettings::Table.into_iden(), "app-settings-app-updated"),
+        (
+            ApplicationGlobalSettings::Table.into_iden(),
+            "app-globalsettings-app-updated",
+        ),
+        (
+            ApplicationUserSettings::Table.into_iden(),
+            "app-usersettings-app-updated",
+        ),
+        (
+            ApplicationScopedSettings::Table.into_iden(),
+            "app-scopedsettings-app-updated",
+        ),
+    ]
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (table, name) in indexes() {
+            // rows written before creates and deletes bumped updated_at would never show up in a refresh
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(table.clone())
+                        .value(
+                            SettingsColumn::UpdatedAt,
+                            Expr::cust("GREATEST(`created_at`, `updated_at`, `deleted_at`)"),
+                        )
+                        .and_where(Expr::cust("`updated_at` < GREATEST(`created_at`, `deleted_at`)"))
+                        .to_owned(),
+                )
+                .await?;
+
+            manager
+                .create_index(
+                    Index::create()
+                        .if_not_exists()
+                        .name(name)
+                        .table(table)
+                        .col(SettingsColumn::Application)
+                        .col(SettingsColumn::UpdatedAt)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (table, name) in indexes() {
+            manager
+                .drop_index(Index::drop().name(name).table(table).to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApplicationSettings {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationGlobalSettings {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationUserSettings {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum ApplicationScopedSettings {
+    Table,
+}
+
+#[derive(DeriveIden)]
+enum SettingsColumn {
+    Application,
+    UpdatedAt,
+}
@@ -1,17 +1,33 @@
 use std::collections::HashMap;
 
+use crate::alias::{RecordId, UnixTimestamp};
 use crate::env::{self, EnvVar};
+use crate::server::PaginationData;
 use crate::util::unix_timestamp;
 use crate::{entities, retry_lock::RetryLock, task_pool::TaskPool};
 use anyhow::{anyhow, Result};
-use entities::applications;
 use entities::applications::Entity as ApplicationEntity;
+use entities::{
+    application_global_settings, application_keys, application_log_retention, application_process_logs,
+    application_process_runs, application_processes, application_scoped_settings, application_settings,
+    application_settings_history, application_user_settings, applications,
+};
 use migration::IndexCreateStatement;
-use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, TransactionTrait,
+};
+use std::time::Duration;
 use uuid::Uuid;
 
+use self::keys::{ApplicationKey, CredentialRevisions};
+use self::process::query::LogQuery;
+use self::process::tail::{LogSubscription, LogTailFilter};
+use self::process::writer::{LogWriter, LogWriterConfig, LogWriters};
 use self::process::ApplicationProcess;
 
+pub mod keys;
 pub mod process;
 pub mod settings;
 
@@ -24,9 +40,47 @@ where
     pub database_core: DatabaseConnection,
     pub tasks: TaskPool,
     pub locks: RetryLock,
+    /// process log writers, one per application
+    pub logs: LogWriters,
+    /// changes to application credentials, so cached authentications can be dropped
+    pub credentials: CredentialRevisions,
     pub extension: Extension,
 }
 
+impl<Extension> ApplicationState<Extension>
+where
+    Extension: Clone,
+{
+    /// state with a single task worker and no cached logs, locks or credential changes
+    pub fn new(
+        database: DatabaseConnection,
+        database_core: DatabaseConnection,
+        extension: Extension,
+    ) -> ApplicationState<Extension> {
+        ApplicationState {
+            database,
+            database_core,
+            tasks: TaskPool::new(1),
+            locks: RetryLock::default(),
+            logs: LogWriters::default(),
+            credentials: CredentialRevisions::default(),
+            extension,
+        }
+    }
+
+    /// replace the task pool used by the state
+    pub fn with_tasks(mut self, tasks: TaskPool) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    /// replace the retry locks used by the state
+    pub fn with_locks(mut self, locks: RetryLock) -> Self {
+        self.locks = locks;
+        self
+    }
+}
+
 #[derive(Clone)]
 pub struct Application<Extension>
 where
@@ -34,6 +88,7 @@ where
 {
     pub state: ApplicationState<Extension>,
     record: applications::Model,
+    key: Option<application_keys::Model>,
 }
 
 impl<Extension> Application<Extension>
@@ -75,6 +130,7 @@ where
             Ok(Application {
                 state: app_state.clone(),
                 record,
+                key: None,
             })
         } else {
             Err(anyhow!("Failed to register application"))
@@ -83,6 +139,8 @@ where
 
     /// regenerate the secret tied to this application.
     /// useful in case credentials get leaked
+    ///
+    /// note: this immediately invalidates the old secret. Use `rotate_key` for a rotation that does not break running instances
     pub async fn regen_hashes(&mut self) -> anyhow::Result<()> {
         let timestamp = unix_timestamp();
         let name = self.record.name.clone();
@@ -92,6 +150,7 @@ where
         let hash = format!("{:x}", md5::compute(seed));
         let hash_secret = format!("{:x}", md5::compute(seed_secret));
 
+        self.state.credentials.bump(&self.record.hash);
         self.record.hash = hash;
         self.record.hash_secret = hash_secret;
         self.record.updated_at = unix_timestamp();
@@ -103,6 +162,7 @@ where
     }
 
     /// gets application record information based off the supplied identifiying hash and secret
+    /// the secret can either be the application secret or the secret of any active application key
     pub async fn get(
         hash: &str,
         secret: &str,
@@ -112,22 +172,138 @@ where
             .filter(
                 Condition::all()
                     .add(applications::Column::Hash.eq(hash))
-                    .add(applications::Column::HashSecret.eq(secret))
                     .add(applications::Column::DeletedAt.eq(0)),
             )
             .one(&app_state.database_core)
             .await?;
 
-        if let Some(record) = model {
+        let record = match model {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        if record.hash_secret == secret {
+            return Ok(Some(Application {
+                state: app_state.clone(),
+                record,
+                key: None,
+            }));
+        }
+
+        let key = ApplicationKey::<Extension>::find_by_secret(record.id, secret, &app_state.database_core).await?;
+        if let Some(key) = key {
+            ApplicationKey::<Extension>::touch(&key, &app_state.database_core);
             Ok(Some(Application {
                 state: app_state.clone(),
                 record,
+                key: Some(key),
             }))
         } else {
             Ok(None)
         }
     }
 
+    /// gets every credential that is currently able to authenticate the application matching the hash.
+    /// one application is returned for the application secret and one for each active key
+    pub(crate) async fn credentials(
+        hash: &str,
+        app_state: &ApplicationState<Extension>,
+    ) -> anyhow::Result<Vec<Application<Extension>>> {
+        let model = ApplicationEntity::find()
+            .filter(
+                Condition::all()
+                    .add(applications::Column::Hash.eq(hash))
+                    .add(applications::Column::DeletedAt.eq(0)),
+            )
+            .one(&app_state.database_core)
+            .await?;
+
+        let record = match model {
+            Some(record) => record,
+            None => return Ok(Vec::new()),
+        };
+
+        let application = Application {
+            state: app_state.clone(),
+            record,
+            key: None,
+        };
+
+        let mut credentials = ApplicationKey::list(&application)
+            .await?
+            .into_iter()
+            .map(|key| Application {
+                key: Some(key.record().clone()),
+                ..application.clone()
+            })
+            .collect::<Vec<_>>();
+        credentials.insert(0, application);
+
+        Ok(credentials)
+    }
+
+    /// gets the application matching the hash regardless of its deletion status.
+    /// this does not check any credentials and is intended for administration
+    pub async fn find(
+        hash: &str,
+        app_state: &ApplicationState<Extension>,
+    ) -> anyhow::Result<Option<Application<Extension>>> {
+        let model = ApplicationEntity::find()
+            .filter(applications::Column::Hash.eq(hash))
+            .one(&app_state.database_core)
+            .await?;
+
+        Ok(model.map(|record| Application {
+            state: app_state.clone(),
+            record,
+            key: None,
+        }))
+    }
+
+    /// list registered applications. Pages start at 1
+    pub async fn list(
+        page: u32,
+        limit: u32,
+        include_deleted: bool,
+        app_state: &ApplicationState<Extension>,
+    ) -> anyhow::Result<(Vec<Application<Extension>>, PaginationData)> {
+        let page = page.max(1);
+        let limit = limit.max(1);
+
+        let mut condition = Condition::all();
+        if !include_deleted {
+            condition = condition.add(applications::Column::DeletedAt.eq(0));
+        }
+
+        let paginator = ApplicationEntity::find()
+            .filter(condition)
+            .order_by_asc(applications::Column::Id)
+            .paginate(&app_state.database_core, limit as u64);
+
+        let totals = paginator.num_items_and_pages().await?;
+        let models = paginator.fetch_page((page - 1) as u64).await?;
+
+        let applications = models
+            .into_iter()
+            .map(|record| Application {
+                state: app_state.clone(),
+                record,
+                key: None,
+            })
+            .collect::<Vec<_>>();
+
+        let pagination = PaginationData {
+            total_results: totals.number_of_items as u32,
+            total_pages: totals.number_of_pages as u32,
+            page,
+            limit,
+            showing: applications.len(),
+            term: String::new(),
+        };
+
+        Ok((applications, pagination))
+    }
+
     /// Attempts to autoload  the application based off the application .env settings
     /// If no application can be found. It will register a new one and save the information into the .env
     pub async fn env(state: &ApplicationState<Extension>) -> anyhow::Result<Application<Extension>> {
@@ -172,25 +348,401 @@ where
         self.record.host.as_str()
     }
 
+    /// get the record id of the application
+    pub fn id(&self) -> RecordId {
+        self.record.id
+    }
+
+    /// get the identifying hash of the application
+    pub fn hash(&self) -> &str {
+        self.record.hash.as_str()
+    }
+
+    /// unix timestamp of when the application was soft deleted. 0 means the application is active
+    pub fn deleted_at(&self) -> UnixTimestamp {
+        self.record.deleted_at
+    }
+
+    /// rename the application
+    pub async fn rename(&mut self, name: &str) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        let mut active: applications::ActiveModel = self.record.clone().into();
+        active.name = ActiveValue::Set(name.to_string());
+        active.updated_at = ActiveValue::Set(timestamp);
+        active.update(&self.state.database_core).await?;
+
+        self.record.name = name.to_string();
+        self.record.updated_at = timestamp;
+        Ok(())
+    }
+
+    /// change the host tied to the application
+    pub async fn set_host(&mut self, host: &str) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        let mut active: applications::ActiveModel = self.record.clone().into();
+        active.host = ActiveValue::Set(host.to_string());
+        active.updated_at = ActiveValue::Set(timestamp);
+        active.update(&self.state.database_core).await?;
+
+        self.record.host = host.to_string();
+        self.record.updated_at = timestamp;
+        Ok(())
+    }
+
+    /// soft delete the application. Processes, runs, logs, retention rules, keys and settings tied to the application are soft deleted with it
+    pub async fn delete(&mut self) -> anyhow::Result<()> {
+        if self.record.deleted_at > 0 {
+            return Ok(());
+        }
+
+        let timestamp = unix_timestamp();
+        self.cascade_deleted_at(0, timestamp).await?;
+        self.state.credentials.bump(&self.record.hash);
+
+        self.record.deleted_at = timestamp;
+        self.record.updated_at = timestamp;
+        Ok(())
+    }
+
+    /// restore a soft deleted application.
+    /// only the rows that were deleted along with the application are restored
+    pub async fn restore(&mut self) -> anyhow::Result<()> {
+        if self.record.deleted_at == 0 {
+            return Ok(());
+        }
+
+        self.cascade_deleted_at(self.record.deleted_at, 0).await?;
+
+        self.record.deleted_at = 0;
+        self.record.updated_at = unix_timestamp();
+        Ok(())
+    }
+
+    /// moves the application and everything tied to it from one deleted_at value to another inside a single transaction
+    async fn cascade_deleted_at(&self, from: UnixTimestamp, to: UnixTimestamp) -> anyhow::Result<()> {
+        let id = self.record.id;
+        let txn = self.state.database_core.begin().await?;
+
+        ApplicationEntity::update_many()
+            .col_expr(applications::Column::DeletedAt, Expr::value(to))
+            .col_expr(applications::Column::UpdatedAt, Expr::value(unix_timestamp()))
+            .filter(applications::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+
+        cascade::<application_processes::Entity, _>(
+            &txn,
+            (
+                application_processes::Column::Application,
+                application_processes::Column::UpdatedAt,
+                application_processes::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_process_logs::Entity, _>(
+            &txn,
+            (
+                application_process_logs::Column::Application,
+                application_process_logs::Column::UpdatedAt,
+                application_process_logs::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_process_runs::Entity, _>(
+            &txn,
+            (
+                application_process_runs::Column::Application,
+                application_process_runs::Column::UpdatedAt,
+                application_process_runs::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_settings::Entity, _>(
+            &txn,
+            (
+                application_settings::Column::Application,
+                application_settings::Column::UpdatedAt,
+                application_settings::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_global_settings::Entity, _>(
+            &txn,
+            (
+                application_global_settings::Column::Application,
+                application_global_settings::Column::UpdatedAt,
+                application_global_settings::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_user_settings::Entity, _>(
+            &txn,
+            (
+                application_user_settings::Column::Application,
+                application_user_settings::Column::UpdatedAt,
+                application_user_settings::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_scoped_settings::Entity, _>(
+            &txn,
+            (
+                application_scoped_settings::Column::Application,
+                application_scoped_settings::Column::UpdatedAt,
+                application_scoped_settings::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_settings_history::Entity, _>(
+            &txn,
+            (
+                application_settings_history::Column::Application,
+                application_settings_history::Column::UpdatedAt,
+                application_settings_history::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_keys::Entity, _>(
+            &txn,
+            (
+                application_keys::Column::Application,
+                application_keys::Column::UpdatedAt,
+                application_keys::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        cascade::<application_log_retention::Entity, _>(
+            &txn,
+            (
+                application_log_retention::Column::Application,
+                application_log_retention::Column::UpdatedAt,
+                application_log_retention::Column::DeletedAt,
+            ),
+            id,
+            from,
+            to,
+        )
+        .await?;
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// get the secret of the credentials that were used to load this application
+    pub fn secret(&self) -> &str {
+        match self.key.as_ref() {
+            Some(key) => key.hash_secret.as_str(),
+            None => self.record.hash_secret.as_str(),
+        }
+    }
+
+    /// sign an outgoing request with the credentials of this application
+    pub fn sign(&self, request: &mut reqwest::Request) -> anyhow::Result<()> {
+        crate::signing::sign_request(request, &self.record.hash, self.secret())
+    }
+
     /// get the desired process
     pub async fn process(&self, name: &str) -> anyhow::Result<ApplicationProcess<Extension>> {
         ApplicationProcess::get(self, name).await
     }
+
+    /// list all processes that belong to this application
+    pub async fn processes(&self) -> anyhow::Result<Vec<ApplicationProcess<Extension>>> {
+        ApplicationProcess::list(self).await
+    }
+
+    /// a query over the logs of every process of this application
+    pub fn logs(&self) -> LogQuery {
+        LogQuery::new(self.record.id)
+    }
+
+    /// configure how process logs of this application are buffered and written.
+    /// replaces the writer for every instance of this application that shares the same `ApplicationState`
+    pub fn with_log_writer(self, config: LogWriterConfig) -> Self {
+        self.state.logs.start(self.record.id, &self.state.database_core, config);
+        self
+    }
+
+    /// the writer process logs of this application go through. Started with the default configuration on first use
+    pub fn log_writer(&self) -> LogWriter {
+        self.state.logs.get_or_start(self.record.id, &self.state.database_core)
+    }
+
+    /// log entries of every process as they are written, starting with up to `replay` recent entries.
+    /// entries written through any instance of this application that shares the same `ApplicationState` are included.
+    /// the subscription ends when the log writer is replaced through `with_log_writer`
+    pub fn tail_logs(&self, filter: LogTailFilter, replay: usize) -> LogSubscription {
+        self.log_writer().subscribe(filter, replay)
+    }
+
+    /// write every buffered process log. Call before shutting down so no logs are lost
+    pub async fn flush_logs(&self) {
+        if let Some(writer) = self.state.logs.get(self.record.id) {
+            writer.flush().await;
+        }
+    }
+
+    /// issue a new named key that can be used in place of the application secret
+    /// an `expires_at` of 0 means the key never expires
+    pub async fn issue_key(
+        &self,
+        name: &str,
+        scopes: &[&str],
+        expires_at: UnixTimestamp,
+    ) -> anyhow::Result<ApplicationKey<Extension>> {
+        ApplicationKey::create(self, name, scopes, expires_at).await
+    }
+
+    /// get the newest active key that matches the supplied name
+    pub async fn key(&self, name: &str) -> anyhow::Result<Option<ApplicationKey<Extension>>> {
+        ApplicationKey::get(self, name).await
+    }
+
+    /// list all keys that are currently able to authenticate this application
+    pub async fn keys(&self) -> anyhow::Result<Vec<ApplicationKey<Extension>>> {
+        ApplicationKey::list(self).await
+    }
+
+    /// rotate the named key. The old key stays valid for the supplied grace period.
+    /// if no key exists with the name, a new key is issued with full access
+    pub async fn rotate_key(&self, name: &str, grace: Duration) -> anyhow::Result<ApplicationKey<Extension>> {
+        if let Some(mut key) = self.key(name).await? {
+            key.rotate(grace).await
+        } else {
+            self.issue_key(name, &[keys::SCOPE_ALL], 0).await
+        }
+    }
+
+    /// when the credentials used to load this application stop working. None when they do not expire
+    pub(crate) fn credentials_expire_at(&self) -> Option<UnixTimestamp> {
+        self.key
+            .as_ref()
+            .map(|key| key.expires_at)
+            .filter(|expires_at| *expires_at > 0)
+    }
+
+    /// record that the key used to load this application was used again. Written at most once per `TOUCH_INTERVAL`
+    pub(crate) fn touch_key(&mut self) {
+        let timestamp = unix_timestamp();
+        if let Some(key) = self.key.as_mut() {
+            if timestamp - key.last_used_at >= keys::TOUCH_INTERVAL {
+                key.last_used_at = timestamp;
+                ApplicationKey::<Extension>::touch(key, &self.state.database_core);
+            }
+        }
+    }
+
+    /// checks if the credentials used to load this application were granted the scope.
+    /// the application secret itself is always granted every scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match self.key.as_ref() {
+            Some(key) => keys::key_has_scope(key, scope),
+            None => true,
+        }
+    }
+}
+
+/// updates the deleted_at column of every row tied to the application that currently matches `from`.
+/// columns are supplied as (application, updated_at, deleted_at)
+async fn cascade<E, C>(
+    db: &C,
+    columns: (E::Column, E::Column, E::Column),
+    application_id: RecordId,
+    from: UnixTimestamp,
+    to: UnixTimestamp,
+) -> anyhow::Result<()>
+where
+    E: EntityTrait,
+    C: ConnectionTrait,
+{
+    let (application, updated_at, deleted_at) = columns;
+    E::update_many()
+        .col_expr(deleted_at, Expr::value(to))
+        .col_expr(updated_at, Expr::value(unix_timestamp()))
+        .filter(application.eq(application_id))
+        .filter(deleted_at.eq(from))
+        .exec(db)
+        .await?;
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use sea_orm::sea_query::Expr;
+    use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+    use tower::ServiceExt;
     use tracing_test::traced_test;
 
     use super::ApplicationState;
+    use crate::app::process::record::LogRecord;
+    use crate::app::process::retention::PurgeConfig;
+    use crate::app::process::run::{ProcessHealth, RunStatus};
+    use crate::app::process::writer::{BackpressurePolicy, LogWriterConfig};
     use crate::app::process::LogLevel;
+    use crate::app::settings::binding::SettingsBinding;
+    use crate::app::settings::events::BroadcastTransport;
+    use crate::app::settings::export::ImportMode;
+    use crate::app::settings::schema::{SettingSchema, SettingValueType};
+    use crate::app::settings::scope::{ResolvedSetting, SettingScopes, SettingSource};
+    use crate::app::settings::secret::{is_encrypted, SecretKey, REDACTED};
     use crate::app::settings::{ApplicationSettingType, ApplicationSettings};
     use crate::app::Application;
+    use crate::cache::CacheDuration;
     use crate::database;
-    use crate::retry_lock::RetryLock;
-    use crate::task_pool::TaskPool;
+    use crate::entities::{application_global_settings, application_process_logs, application_settings};
+    use crate::server::auth::{ApplicationAuth, HEADER_APPLICATION_ID, HEADER_APPLICATION_SECRET};
+    use crate::server::signature::{require_signature, SignatureVerifier};
+    use crate::signing;
     use crate::tokio;
+    use crate::util::unix_timestamp;
+
+    /// state connected to the local test database
+    async fn mock_state() -> ApplicationState<()> {
+        tracing::info!("Setting up database connection");
+        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
+        ApplicationState::new(db.clone(), db, ())
+    }
 
     #[derive(Clone, Default)]
     struct DemoExtension {
@@ -205,13 +757,7 @@ mod tests {
         tracing::info!("Setting up database connection");
         let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
 
-        let state = ApplicationState::<DemoExtension> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: DemoExtension::default(),
-        };
+        let state = ApplicationState::new(db.clone(), db, DemoExtension::default());
 
         let _ = state.database.close().await;
     }
@@ -219,31 +765,14 @@ mod tests {
     #[traced_test]
     #[tokio::test]
     pub async fn appstate_noextension_test() {
-        tracing::info!("Setting up database connection");
-        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
-
-        let state = ApplicationState::<()> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: (),
-        };
+        let state = mock_state().await;
 
         let _ = state.database.close().await;
     }
     #[traced_test]
     #[tokio::test]
     pub async fn app_register_test() {
-        tracing::info!("Setting up database connection");
-        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
-        let state = ApplicationState::<()> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: (),
-        };
+        let state = mock_state().await;
 
         let app = Application::register("mock", "localhost", &state)
             .await
@@ -260,15 +789,7 @@ mod tests {
     #[traced_test]
     #[tokio::test]
     pub async fn app_log_test() {
-        tracing::info!("Setting up database connection");
-        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
-        let state = ApplicationState::<()> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: (),
-        };
+        let state = mock_state().await;
 
         let app = Application::register("mock_settings", "localhost", &state)
             .await
@@ -287,20 +808,410 @@ mod tests {
         let _ = futures::future::join_all(handles).await;
     }
 
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_writer_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_writer", "localhost", &state)
+            .await
+            .expect("Application did not create")
+            .with_log_writer(LogWriterConfig {
+                batch_size: 4,
+                ..Default::default()
+            });
+
+        let process = app.process("global").await?;
+        for _ in 0..10 {
+            process.log_info("same line");
+        }
+        process.log_error("last line").await?;
+        app.flush_logs().await;
+
+        let written = application_process_logs::Entity::find()
+            .filter(application_process_logs::Column::Process.eq(process.id()))
+            .count(&state.database_core)
+            .await?;
+        assert_eq!(written, 11);
+        assert_eq!(app.log_writer().dropped(), 0);
+        assert_eq!(app.log_writer().pending(), 0);
+
+        // nothing is written until the interval, so everything past the capacity is dropped
+        let app = app.with_log_writer(LogWriterConfig {
+            capacity: 2,
+            interval: Duration::from_secs(60),
+            policy: BackpressurePolicy::DropNewest,
+            ..Default::default()
+        });
+        let process = app.process("global").await?;
+        let handles = (0..5).map(|_| process.log_debug("flood")).collect::<Vec<_>>();
+        assert_eq!(app.log_writer().dropped(), 3);
+        app.flush_logs().await;
+
+        let results = futures::future::join_all(handles).await;
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 2);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_record_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_record", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let process = app.process("global").await?;
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        process
+            .write(
+                LogRecord::new(LogLevel::Warning, "slow query")
+                    .with_field("ms", 1200)
+                    .with_sub_id("guild-1")
+                    .with_correlation_id(&correlation_id),
+            )
+            .await?;
+        process.log_info("untracked").await?;
+
+        let rows = application_process_logs::Entity::find()
+            .filter(application_process_logs::Column::Application.eq(app.id()))
+            .filter(application_process_logs::Column::CorrelationId.eq(correlation_id.as_str()))
+            .all(&state.database_core)
+            .await?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].fields.as_deref(), Some(r#"{"ms":1200}"#));
+        assert_eq!(rows[0].sub_id, "guild-1");
+        assert_eq!(rows[0].module, file!());
+        assert!(rows[0].line > 0);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_level_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_level", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let process = app.process("quiet").await?;
+        let transport = BroadcastTransport::new();
+        let mut writer = ApplicationSettings::load(&app).await?.with_transport(transport.clone());
+        process.register_log_levels(&mut writer).await?;
+        writer.set_global("process.quiet.log_level", "warning").await?.await??;
+
+        let (settings, _handle) = ApplicationSettings::load(&app)
+            .await?
+            .with_transport(transport)
+            .auto_refresh(Duration::from_secs(60));
+        let _watch = process.watch_log_levels(&settings).await;
+        assert_eq!(process.log_levels().persist(), LogLevel::Warning as i8);
+
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        let record = |level, content| LogRecord::new(level, content).with_correlation_id(&correlation_id);
+        process.write(record(LogLevel::Info, "skipped")).await?;
+        process.write(record(LogLevel::Warning, "written")).await?;
+
+        // changed at runtime, picked up through the transport
+        writer.set_global("process.quiet.log_level", "info").await?.await??;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while process.log_levels().persist() != LogLevel::Info as i8 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        process.write(record(LogLevel::Info, "written after change")).await?;
+        process.write(record(LogLevel::Debug, "still skipped")).await?;
+
+        let rows = application_process_logs::Entity::find()
+            .filter(application_process_logs::Column::Application.eq(app.id()))
+            .filter(application_process_logs::Column::CorrelationId.eq(correlation_id.as_str()))
+            .all(&state.database_core)
+            .await?;
+        let mut contents = rows.into_iter().map(|row| row.content).collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(contents, vec!["written", "written after change"]);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_stream_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_stream", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let router = crate::server::logs::router::<ApplicationAuth<()>, ()>().with_state(ApplicationAuth::new(&state));
+        tokio::spawn(axum::Server::from_tcp(listener)?.serve(router.into_make_service()));
+
+        // keys without the logs scope can not tail anything
+        let key = app.issue_key("settings", &["settings.read"], 0).await?;
+        let rejected = reqwest::Client::new()
+            .get(format!("http://{}/logs/stream", addr))
+            .header(HEADER_APPLICATION_ID, app.hash())
+            .header(HEADER_APPLICATION_SECRET, key.secret())
+            .send()
+            .await?;
+        assert_eq!(rejected.status(), reqwest::StatusCode::FORBIDDEN);
+
+        // the route is subscribed once the response starts
+        let mut response = reqwest::Client::new()
+            .get(format!("http://{}/logs/stream", addr))
+            .header(HEADER_APPLICATION_ID, app.hash())
+            .header(HEADER_APPLICATION_SECRET, app.secret())
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // written through an instance of the application that the route never sees
+        let writer = Application::get(app.hash(), app.secret(), &state)
+            .await?
+            .expect("Application was not found");
+        writer
+            .process("streamed")
+            .await?
+            .log_warning("through the route")
+            .await?;
+
+        let mut received = String::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !received.contains("through the route") {
+                match response.chunk().await? {
+                    Some(chunk) => received.push_str(&String::from_utf8_lossy(&chunk)),
+                    None => break,
+                }
+            }
+            anyhow::Ok(())
+        })
+        .await??;
+        assert!(received.contains("event: log"));
+        assert!(received.contains("through the route"));
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_signature_nested_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_signature_nested", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let api = axum::Router::new()
+            .route(
+                "/echo",
+                axum::routing::post(
+                    |axum::Extension(application): axum::Extension<Application<()>>| async move {
+                        application.hash().to_string()
+                    },
+                ),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                SignatureVerifier::new(&state),
+                require_signature::<()>,
+            ));
+        let router = axum::Router::new().nest("/api", api);
+
+        // the client signs the full path, including the prefix the router was nested under
+        let body = r#"{"ping":true}"#;
+        let timestamp = unix_timestamp();
+        let signature = signing::signature(app.secret(), "POST", "/api/echo?x=1", body.as_bytes(), timestamp);
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/api/echo?x=1")
+            .header(signing::HEADER_SIGNATURE_APPLICATION, app.hash())
+            .header(signing::HEADER_SIGNATURE_TIMESTAMP, timestamp.to_string())
+            .header(signing::HEADER_SIGNATURE, signature)
+            .body(axum::body::Body::from(body))?;
+
+        let response = router.oneshot(request).await?;
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let mut returned = Vec::new();
+        let mut response_body = response.into_body();
+        while let Some(chunk) = axum::body::HttpBody::data(&mut response_body).await {
+            returned.extend_from_slice(&chunk?);
+        }
+        assert_eq!(returned, app.hash().as_bytes());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_query_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_query", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let process = app.process("query").await?;
+        let sub_id = uuid::Uuid::new_v4().to_string();
+        for i in 0..5 {
+            process.log(LogLevel::Info, &format!("request {i} done"), Some(&sub_id));
+        }
+        process.log(LogLevel::Error, "request failed with timeout", Some(&sub_id));
+        process.log(LogLevel::Debug, "unrelated", None).await?;
+
+        let query = process.logs().with_sub_id(&sub_id).with_limit(4).with_totals();
+        let first = query.fetch(&state.database_core).await?;
+        assert_eq!(first.data.len(), 4);
+        assert_eq!(first.pagination.total_results, 6);
+        assert_eq!(first.pagination.total_pages, 2);
+        assert_eq!(first.pagination.page, 1);
+        assert_eq!(first.data[0].content, "request failed with timeout");
+
+        let cursor = first.cursor.expect("No cursor for the second page");
+        let second = query
+            .clone()
+            .with_cursor(cursor)
+            .with_page(2)
+            .fetch(&state.database_core)
+            .await?;
+        assert_eq!(second.data.len(), 2);
+        assert_eq!(second.pagination.page, 2);
+        assert_eq!(second.cursor, None);
+
+        let errors = app
+            .logs()
+            .with_sub_id(&sub_id)
+            .with_severity(LogLevel::Warning)
+            .with_search("timeout")
+            .fetch(&state.database_core)
+            .await?;
+        assert_eq!(errors.data.len(), 1);
+        assert_eq!(errors.pagination.term, "timeout");
+        assert_eq!(errors.pagination.total_results, 0);
+
+        // wildcards are matched literally
+        let wildcard = app
+            .logs()
+            .with_sub_id(&sub_id)
+            .with_search("%")
+            .fetch(&state.database_core)
+            .await?;
+        assert!(wildcard.data.is_empty());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_log_retention_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_log_retention", "localhost", &state)
+            .await
+            .expect("Application did not create");
+        let process = app.process("retention").await?;
+
+        let day = Duration::from_secs(86400);
+        app.set_log_retention(None, Some(LogLevel::Debug), day * 3, false)
+            .await?;
+        app.set_log_retention(None, Some(LogLevel::Error), day * 90, true)
+            .await?;
+
+        // entries that are 10 days old. Only the debug entries are past their retention
+        let old = crate::util::unix_timestamp() - 10 * 86400;
+        let mut handles = Vec::new();
+        for (level, content) in [(LogLevel::Debug, "old debug"), (LogLevel::Error, "old error")] {
+            let mut model = LogRecord::new(level, content).into_model(app.id(), process.id());
+            model.created_at = sea_orm::ActiveValue::Set(old);
+            handles.push(app.log_writer().write(model));
+        }
+        handles.push(process.log_debug("new debug"));
+        futures::future::try_join_all(handles).await?;
+
+        let report = app.purge_logs(&PurgeConfig::default()).await?;
+        assert_eq!(report.deleted, 1);
+        assert_eq!(report.archived, 0);
+
+        let remaining = application_process_logs::Entity::find()
+            .filter(application_process_logs::Column::Process.eq(process.id()))
+            .filter(application_process_logs::Column::Content.eq("old debug"))
+            .count(&state.database_core)
+            .await?;
+        assert_eq!(remaining, 0);
+
+        // errors archive, so with a short retention they end up on disk before they are deleted
+        let dir = std::env::temp_dir().join(format!("levelcrush-purge-{}", uuid::Uuid::new_v4()));
+        app.set_log_retention(None, Some(LogLevel::Error), day, true).await?;
+        let report = app
+            .purge_logs(&PurgeConfig {
+                batch_size: 1,
+                archive_dir: Some(dir.clone()),
+            })
+            .await?;
+        assert!(report.archived >= 1);
+        assert_eq!(report.files.len() as u64, report.archived);
+        assert!(report.files.iter().all(|file| file.exists()));
+        std::fs::remove_dir_all(&dir)?;
+
+        app.remove_log_retention(None, Some(LogLevel::Error)).await?;
+        assert_eq!(app.log_retention().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_process_run_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_process_run", "localhost", &state)
+            .await
+            .expect("Application did not create");
+        let process = app.process("worker").await?;
+
+        let run = process.start_run().await?;
+        assert_eq!(run.status, RunStatus::Running);
+        assert_eq!(process.run_id(), Some(run.id));
+        assert!(process.start_run().await.is_err());
+        assert_eq!(process.health(Duration::from_secs(60)).await?, ProcessHealth::Healthy);
+
+        // once a second has passed without a heartbeat, a max age of 0 flags the run
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let flagged = app.flag_stale_runs(Duration::from_secs(0)).await?;
+        assert!(flagged.iter().any(|stale| stale.id == run.id));
+        assert!(app.stale_runs().await?.iter().any(|stale| stale.id == run.id));
+
+        // only runs that were flagged by the call are returned
+        let flagged = app.flag_stale_runs(Duration::from_secs(0)).await?;
+        assert!(!flagged.iter().any(|stale| stale.id == run.id));
+
+        // the heartbeat brings the run back
+        process.heartbeat().await?;
+        assert!(!app.stale_runs().await?.iter().any(|stale| stale.id == run.id));
+
+        process.finish_run(RunStatus::Succeeded).await?;
+        assert_eq!(process.run_id(), None);
+        assert!(process.heartbeat().await.is_err());
+        assert_eq!(
+            process.health(Duration::from_secs(60)).await?,
+            ProcessHealth::Stopped(RunStatus::Succeeded)
+        );
+        assert_eq!(process.runs(1).await?[0].id, run.id);
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     pub async fn app_env_load_test() {
         let _ = dotenvy::dotenv();
 
-        tracing::info!("Setting up database connection");
-        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
-        let state = ApplicationState::<()> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: (),
-        };
+        let state = mock_state().await;
 
         let app = Application::env(&state).await.expect("Application did not create");
 
@@ -317,6 +1228,560 @@ mod tests {
         let _ = futures::future::join_all(handles).await;
     }
 
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_key_rotation_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_keys", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut key = app.issue_key("deploy", &["settings.read"], 0).await?;
+        let rotated = app.rotate_key("deploy", Duration::from_secs(60)).await?;
+        assert_ne!(key.secret(), rotated.secret());
+        assert!(rotated.has_scope("settings.read"));
+        assert!(!rotated.has_scope("settings.write"));
+
+        // both the old key (inside the grace window) and the new key should authenticate
+        let old_auth = Application::get(&app.record.hash, key.secret(), &state).await?;
+        let new_auth = Application::get(&app.record.hash, rotated.secret(), &state).await?;
+        assert!(old_auth.is_some());
+        assert!(new_auth.is_some());
+        assert!(!new_auth.unwrap().has_scope("settings.write"));
+
+        // revoking the old key should stop it from authenticating before the grace window runs out
+        key.revoke().await?;
+        let old_auth = Application::get(&app.record.hash, key.secret(), &state).await?;
+        assert!(old_auth.is_none());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_auth_cache_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let mut app = Application::register("mock_auth_cache", "localhost", &state)
+            .await
+            .expect("Application did not create");
+        let hash = app.hash().to_string();
+        let auth = ApplicationAuth::new(&state).cache_duration(CacheDuration::OneHour);
+
+        // revoking drops the cached authentication right away
+        let mut key = app.issue_key("deploy", &["settings.read"], 0).await?;
+        assert!(auth.authenticate(&hash, key.secret()).await?.is_some());
+        key.revoke().await?;
+        assert!(auth.authenticate(&hash, key.secret()).await?.is_none());
+
+        // the cache never outlives the key
+        let short = app.issue_key("short", &["settings.read"], unix_timestamp() + 1).await?;
+        assert!(auth.authenticate(&hash, short.secret()).await?.is_some());
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(auth.authenticate(&hash, short.secret()).await?.is_none());
+
+        let secret = app.secret().to_string();
+        assert!(auth.authenticate(&hash, &secret).await?.is_some());
+        app.delete().await?;
+        assert!(auth.authenticate(&hash, &secret).await?.is_none());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_auth_instances_test() -> anyhow::Result<()> {
+        // two instances, each with their own state and cache
+        let state = mock_state().await;
+        let other = mock_state().await;
+
+        let app = Application::register("mock_auth_instances", "localhost", &state)
+            .await
+            .expect("Application did not create");
+        let hash = app.hash().to_string();
+        let auth = ApplicationAuth::new(&other).cache_duration(CacheDuration::Custom(1));
+
+        let mut key = app.issue_key("deploy", &["settings.read"], 0).await?;
+        assert!(auth.authenticate(&hash, key.secret()).await?.is_some());
+
+        // the other instance only finds out once its cached entry runs out
+        key.revoke().await?;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(auth.authenticate(&hash, key.secret()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn app_lifecycle_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let mut app = Application::register("mock_lifecycle", "localhost", &state)
+            .await
+            .expect("Application did not create");
+        let hash = app.hash().to_string();
+        let secret = app.record.hash_secret.clone();
+
+        app.rename("mock_renamed").await?;
+        app.set_host("levelcrush.local").await?;
+        let process = app.process("global").await?;
+        process.log_info("before delete").await?;
+
+        app.delete().await?;
+        assert!(Application::get(&hash, &secret, &state).await?.is_none());
+
+        let mut deleted = Application::find(&hash, &state)
+            .await?
+            .expect("Application was hard deleted");
+        assert_eq!(deleted.name(), "mock_renamed");
+        assert_eq!(deleted.host(), "levelcrush.local");
+        assert!(deleted.deleted_at() > 0);
+
+        deleted.restore().await?;
+        assert!(Application::get(&hash, &secret, &state).await?.is_some());
+
+        let (applications, pagination) = Application::list(1, 10, false, &state).await?;
+        assert!(applications.len() <= 10);
+        assert_eq!(pagination.showing, applications.len());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_refresh_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_refresh", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        // two independent instances of the same application
+        let mut writer = ApplicationSettings::load(&app).await?;
+        let mut reader = ApplicationSettings::load(&app).await?;
+
+        writer.set_global("mock.refresh", "first").await?.await??;
+        writer.set_user("123", "mock.refresh", "user first").await?.await??;
+        assert_eq!(reader.get_global("mock.refresh"), None);
+
+        reader.refresh().await?;
+        assert_eq!(reader.get_global("mock.refresh"), Some("first".to_string()));
+        assert_eq!(
+            reader.get_user_or_load("123", "mock.refresh").await?,
+            Some("user first".to_string())
+        );
+
+        writer.delete_user("123", "mock.refresh").await?;
+        reader.refresh().await?;
+        assert_eq!(reader.get_user("123", "mock.refresh"), Some("first".to_string()));
+
+        writer.delete_global("mock.refresh").await?;
+        reader.refresh().await?;
+        assert_eq!(reader.get_global("mock.refresh"), None);
+
+        // a soft deleted setting that is created again has to be picked up by other instances
+        application_settings::Entity::update_many()
+            .col_expr(application_settings::Column::DeletedAt, Expr::value(unix_timestamp()))
+            .col_expr(application_settings::Column::UpdatedAt, Expr::value(unix_timestamp()))
+            .filter(application_settings::Column::Application.eq(app.id()))
+            .filter(application_settings::Column::Name.eq("mock.refresh"))
+            .exec(&state.database_core)
+            .await?;
+        reader.refresh().await?;
+        let mut revived = ApplicationSettings::load(&app).await?;
+        revived.set_global("mock.refresh", "revived").await?.await??;
+        reader.refresh().await?;
+        assert_eq!(reader.get_global("mock.refresh"), Some("revived".to_string()));
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_user_cache_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_user_cache", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut writer = ApplicationSettings::load(&app).await?;
+        writer.set_global("mock.user_cache", "global").await?.await??;
+        writer.set_user("1", "mock.user_cache", "one").await?.await??;
+        writer.set_user("2", "mock.user_cache", "two").await?.await??;
+
+        let mut reader = ApplicationSettings::load(&app)
+            .await?
+            .with_user_cache(1, CacheDuration::FiveMinutes);
+        assert!(!reader.is_user_loaded("1"));
+        assert_eq!(reader.get_user("1", "mock.user_cache"), Some("global".to_string()));
+        assert_eq!(
+            reader.get_user_or_load("1", "mock.user_cache").await?,
+            Some("one".to_string())
+        );
+        assert!(reader.is_user_loaded("1"));
+
+        // loading a second user pushes the first one out of the cache
+        assert_eq!(
+            reader.get_user_or_load("2", "mock.user_cache").await?,
+            Some("two".to_string())
+        );
+        assert!(reader.is_user_loaded("2"));
+        assert!(!reader.is_user_loaded("1"));
+        assert_eq!(reader.get_user("1", "mock.user_cache"), Some("global".to_string()));
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_schema_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_schema", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut settings = ApplicationSettings::load(&app).await?;
+        settings
+            .register(
+                SettingSchema::new("mock.limit", SettingValueType::Int)
+                    .with_default("10")
+                    .with_description("how many items to return")
+                    .with_validator(|value| match value.parse::<i64>()? {
+                        1..=100 => Ok(()),
+                        _ => Err(anyhow::anyhow!("must be between 1 and 100")),
+                    }),
+            )
+            .await?;
+
+        assert_eq!(settings.get_global_as::<i64>("mock.limit")?, Some(10));
+        assert!(settings.set_global("mock.limit", "abc").await.is_err());
+        assert!(settings.set_global("mock.limit", "500").await.is_err());
+
+        settings.set_global("mock.limit", "25").await?.await??;
+        settings.set_user("123", "mock.limit", "50").await?.await??;
+        assert_eq!(settings.get_global_as::<i64>("mock.limit")?, Some(25));
+        assert_eq!(settings.get_user_as::<i64>("123", "mock.limit")?, Some(50));
+
+        // the type and default survive a reload, the validator does not
+        let reloaded = ApplicationSettings::load(&app).await?;
+        let schema = reloaded.schema("mock.limit").expect("Schema was not persisted");
+        assert_eq!(schema.value_type(), &SettingValueType::Int);
+        assert_eq!(schema.default_value(), Some("10"));
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_history_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_history", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut settings = ApplicationSettings::load(&app).await?.with_actor("tester");
+        settings.set_global("mock.history", "first").await?.await??;
+        settings.set_global("mock.history", "second").await?.await??;
+        // setting the same value again is not a change
+        settings
+            .set_sync(ApplicationSettingType::Global, "mock.history", "second", None)
+            .await?;
+
+        let history = settings.history("mock.history", None, 10).await?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].old_value, Some("first".to_string()));
+        assert_eq!(history[0].new_value, Some("second".to_string()));
+        assert_eq!(history[0].actor, "tester");
+        assert_eq!(history[1].old_value, None);
+
+        settings.rollback(history[0].id).await?;
+        assert_eq!(settings.get_global("mock.history"), Some("first".to_string()));
+
+        // rolling back the first revision removes the setting entirely
+        settings.rollback(history[1].id).await?;
+        assert_eq!(settings.get_global("mock.history"), None);
+        assert_eq!(settings.history("mock.history", None, 10).await?.len(), 4);
+
+        // quick writes to the same setting are stored in the order they were made
+        let mut handles = Vec::new();
+        for value in ["a", "b", "c", "d"] {
+            handles.push(settings.set_global("mock.history.order", value).await?);
+        }
+        for handle in handles.into_iter() {
+            handle.await??;
+        }
+        let stored = ApplicationSettings::load(&app).await?;
+        assert_eq!(stored.get_global("mock.history.order"), Some("d".to_string()));
+        let history = settings.history("mock.history.order", None, 10).await?;
+        let values = history
+            .iter()
+            .map(|revision| (revision.old_value.as_deref(), revision.new_value.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                (Some("c"), Some("d")),
+                (Some("b"), Some("c")),
+                (Some("a"), Some("b")),
+                (None, Some("a"))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_scope_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_scope", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut writer = ApplicationSettings::load(&app).await?;
+        writer
+            .register(SettingSchema::new("mock.scope", SettingValueType::String).with_default("default"))
+            .await?;
+
+        let scopes = SettingScopes::new().user("1").channel("2").guild("3");
+        let resolved = writer.resolve_or_load("mock.scope", &scopes).await?;
+        assert_eq!(resolved.map(|v| v.source), Some(SettingSource::Default));
+
+        writer.set_global("mock.scope", "global").await?.await??;
+        writer.set_guild("3", "mock.scope", "guild").await?.await??;
+        writer.set_channel("2", "mock.scope", "channel").await?.await??;
+
+        let mut reader = ApplicationSettings::load(&app).await?;
+        let resolved = reader.resolve_or_load("mock.scope", &scopes).await?;
+        assert_eq!(
+            resolved,
+            Some(ResolvedSetting {
+                value: "channel".to_string(),
+                source: SettingSource::Scope(ApplicationSettingType::Channel),
+            })
+        );
+
+        writer.set_user("1", "mock.scope", "user").await?.await??;
+        writer
+            .delete(ApplicationSettingType::Channel, "mock.scope", Some("2".to_string()))
+            .await?;
+        reader.refresh().await?;
+        let resolved = reader.resolve("mock.scope", &scopes);
+        assert_eq!(resolved.map(|v| v.value), Some("user".to_string()));
+
+        let resolved = reader.resolve("mock.scope", &SettingScopes::new().channel("2").guild("3"));
+        assert_eq!(
+            resolved.map(|v| v.source),
+            Some(SettingSource::Scope(ApplicationSettingType::Guild))
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_events_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_events", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let transport = BroadcastTransport::new();
+        let mut writer = ApplicationSettings::load(&app).await?.with_transport(transport.clone());
+        writer.set_global("mock.events.a", "1").await?.await??;
+
+        let reader = ApplicationSettings::load(&app).await?.with_transport(transport);
+        let mut subscription = reader.subscribe("mock.events.");
+        let (reader, _handle) = reader.auto_refresh(Duration::from_secs(60));
+
+        // pushed through the transport well before the next poll
+        writer.set_global("mock.events.a", "2").await?.await??;
+        let event = tokio::time::timeout(Duration::from_secs(5), subscription.recv())
+            .await?
+            .expect("Subscription closed");
+        assert_eq!(event.name, "mock.events.a");
+        assert_eq!(event.value.as_deref(), Some("2"));
+        assert_eq!(reader.read().await.get_global("mock.events.a"), Some("2".to_string()));
+
+        // polling picks up changes from instances that do not share the transport
+        let mut polled = ApplicationSettings::load(&app).await?;
+        let mut subscription = polled.subscribe("mock.events.");
+        writer.delete_global("mock.events.a").await?;
+        polled.refresh().await?;
+        let event = subscription.recv().await.expect("Subscription closed");
+        assert_eq!(event.value, None);
+        assert_eq!(event.origin, "");
+        assert_eq!(polled.get_global("mock.events.a"), None);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_secret_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_secret", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let key = SecretKey::generate();
+        let mut settings = ApplicationSettings::load(&app).await?.with_secret_key(key.clone());
+        settings.set_global("mock.secret.token", "hunter2").await?.await??;
+        settings
+            .set_global("mock.secret.prefixed", "enc:literal")
+            .await?
+            .await??;
+
+        // values stored before the setting became secret are encrypted when it does
+        settings
+            .register(SettingSchema::new("mock.secret.token", SettingValueType::String).with_secret())
+            .await?;
+        settings
+            .set_user("1", "mock.secret.token", "user-token")
+            .await?
+            .await??;
+
+        let stored = application_global_settings::Entity::find()
+            .filter(application_global_settings::Column::Application.eq(app.record.id))
+            .all(&state.database_core)
+            .await?;
+        assert!(stored.iter().all(|model| is_encrypted(&model.value)));
+        assert_eq!(settings.get_global("mock.secret.token"), Some("hunter2".to_string()));
+
+        // plain values that happen to look encrypted are encrypted like any other value
+        settings
+            .register(SettingSchema::new("mock.secret.prefixed", SettingValueType::String).with_secret())
+            .await?;
+        assert_eq!(
+            settings.get_global("mock.secret.prefixed"),
+            Some("enc:literal".to_string())
+        );
+
+        settings.set_guild("7", "mock.secret.plain", "on").await?.await??;
+
+        let export = settings.export(true).await?;
+        assert_eq!(
+            export.global.get("mock.secret.token").map(|v| v.as_str()),
+            Some(REDACTED)
+        );
+        assert_eq!(export.users["1"]["mock.secret.token"], REDACTED);
+        assert_eq!(export.guilds["7"]["mock.secret.plain"], "on");
+        assert!(settings.diff(&export, ImportMode::Merge).await?.is_empty());
+
+        // scoped values are imported as well
+        let mut changed = export.clone();
+        changed.guilds.insert(
+            "7".to_string(),
+            BTreeMap::from([("mock.secret.plain".to_string(), "off".to_string())]),
+        );
+        let changes = settings.import(&changed, ImportMode::Merge, false).await?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].setting_type, ApplicationSettingType::Guild);
+        assert_eq!(
+            settings.list_scope(ApplicationSettingType::Guild, "7").await?,
+            vec![("mock.secret.plain".to_string(), "off".to_string())]
+        );
+
+        let rotated = SecretKey::generate();
+        assert!(settings.rotate_secret_key(rotated.clone()).await? >= 2);
+        assert_eq!(settings.get_global("mock.secret.token"), Some("hunter2".to_string()));
+
+        let mut reader = ApplicationSettings::load(&app).await?.with_secret_key(rotated);
+        assert_eq!(
+            reader.get_user_or_load("1", "mock.secret.token").await?,
+            Some("user-token".to_string())
+        );
+
+        // the old key can no longer read anything
+        let stale = ApplicationSettings::load(&app).await?.with_secret_key(key);
+        assert_eq!(stale.get_global("mock.secret.token"), None);
+
+        Ok(())
+    }
+
+    #[derive(crate::macros::SettingsBinding, Clone, Debug, PartialEq)]
+    #[settings(prefix = "mock.binding.")]
+    pub struct MockSettings {
+        /// command prefix
+        #[setting(default = "!")]
+        pub prefix: String,
+        #[setting(default = 10, user)]
+        pub limit: i64,
+        #[setting(default = 3)]
+        pub retries: u32,
+        /// no default, falls back to the default of the type
+        pub verbose: bool,
+    }
+
+    #[test]
+    pub fn appsetting_binding_test() {
+        let defaults = MockSettings::default();
+        assert_eq!(defaults.prefix, "!");
+        assert_eq!(defaults.limit, 10);
+
+        let schemas = MockSettings::schemas();
+        assert_eq!(schemas[0].name(), "mock.binding.prefix");
+        assert_eq!(schemas[1].value_type(), &SettingValueType::Int);
+        assert_eq!(schemas[1].default_value(), Some("10"));
+
+        let values = defaults.values();
+        assert_eq!(values[0], ("mock.binding.prefix", "!".to_string(), false));
+        assert_eq!(values[1], ("mock.binding.limit", "10".to_string(), true));
+        assert_eq!(defaults.retries, 3);
+        assert_eq!(schemas[2].default_value(), Some("3"));
+        assert_eq!(values[2], ("mock.binding.retries", "3".to_string(), false));
+        assert!(!defaults.verbose);
+        assert_eq!(schemas[3].value_type(), &SettingValueType::Bool);
+        assert_eq!(values[3], ("mock.binding.verbose", "false".to_string(), false));
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    pub async fn appsetting_binding_db_test() -> anyhow::Result<()> {
+        let state = mock_state().await;
+
+        let app = Application::register("mock_binding", "localhost", &state)
+            .await
+            .expect("Application did not create");
+
+        let mut settings = ApplicationSettings::load(&app).await?;
+        settings.register_binding::<MockSettings>().await?;
+        assert_eq!(MockSettings::load_from(&settings, None)?, MockSettings::default());
+
+        let global = MockSettings {
+            prefix: "?".to_string(),
+            limit: 20,
+            retries: 5,
+            verbose: true,
+        };
+        global.save_to(&mut settings, None).await?;
+
+        // only the limit can be overridden by a user
+        let user = MockSettings {
+            prefix: "$".to_string(),
+            limit: 30,
+            retries: 1,
+            verbose: false,
+        };
+        user.save_to(&mut settings, Some("123")).await?;
+
+        assert_eq!(MockSettings::load_from(&settings, None)?, global);
+        let loaded = MockSettings::load_from(&settings, Some("123"))?;
+        assert_eq!(loaded.prefix, "?");
+        assert_eq!(loaded.limit, 30);
+        assert_eq!(loaded.retries, 5);
+
+        Ok(())
+    }
+
     /// this is a bad test. It is really just here to test functionality.
     /// todo: rewrite this for proper test
     #[traced_test]
@@ -324,14 +1789,7 @@ mod tests {
     pub async fn appsetting_test() -> anyhow::Result<()> {
         tracing::info!("Beginning setting test");
 
-        let db = database::connect("mysql://root@localhost/levelcrush", 1).await;
-        let state = ApplicationState::<()> {
-            database: db.clone(),
-            database_core: db,
-            tasks: TaskPool::new(1),
-            locks: RetryLock::default(),
-            extension: (),
-        };
+        let state = mock_state().await;
 
         let app = if let Some(app) = Application::get(
             "082fd059b5e0e43df8710065cd9e6cea", // replace with your own hash
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::Application;
+use crate::{alias::UnixTimestamp, entities::application_keys, util::unix_timestamp};
+use anyhow::anyhow;
+use application_keys::Entity as ApplicationKeyEntity;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+use uuid::Uuid;
+
+/// scope that grants access to everything the application itself can do
+pub const SCOPE_ALL: &str = "*";
+
+/// how long a rotated key stays valid when no grace period is specified
+pub const DEFAULT_ROTATION_GRACE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// how often using a key from an authentication cache updates its last used timestamp
+pub const TOUCH_INTERVAL: i64 = 60;
+
+/// counts changes to the credentials of each application, keyed by the application hash.
+/// bumped when a key is revoked or rotated, the secret is regenerated or the application is deleted,
+/// so anything caching an authenticated application can tell its entry is stale.
+/// only changes made through this instance are counted
+#[derive(Clone, Default)]
+pub struct CredentialRevisions {
+    revisions: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl CredentialRevisions {
+    /// the current revision of the credentials of the application
+    pub fn get(&self, hash: &str) -> u64 {
+        self.revisions
+            .lock()
+            .expect("Credential revisions lock poisoned")
+            .get(hash)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// mark every cached authentication of the application as stale
+    pub fn bump(&self, hash: &str) {
+        *self
+            .revisions
+            .lock()
+            .expect("Credential revisions lock poisoned")
+            .entry(hash.to_string())
+            .or_default() += 1;
+    }
+}
+
+#[derive(Clone)]
+pub struct ApplicationKey<Extension>
+where
+    Extension: Clone,
+{
+    application: Application<Extension>,
+    record: application_keys::Model,
+}
+
+impl<Extension> ApplicationKey<Extension>
+where
+    Extension: Clone,
+{
+    /// issue a new named key for the application.
+    /// an `expires_at` of 0 means the key never expires
+    pub async fn create(
+        application: &Application<Extension>,
+        name: &str,
+        scopes: &[&str],
+        expires_at: UnixTimestamp,
+    ) -> anyhow::Result<ApplicationKey<Extension>> {
+        let timestamp = unix_timestamp();
+        let seed = format!(
+            "{}||{}||{}||{}",
+            timestamp,
+            application.record.hash,
+            name,
+            Uuid::new_v4()
+        );
+        let seed_secret = format!("{}{}{}{}{}", seed, timestamp, name, timestamp, Uuid::new_v4());
+
+        let hash = format!("{:x}", md5::compute(seed));
+        let hash_secret = format!("{:x}", md5::compute(seed_secret));
+
+        let new_key = application_keys::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(application.record.id),
+            hash: ActiveValue::Set(hash),
+            hash_secret: ActiveValue::Set(hash_secret),
+            name: ActiveValue::Set(name.to_string()),
+            scopes: ActiveValue::Set(scopes.join(",")),
+            expires_at: ActiveValue::Set(expires_at),
+            last_used_at: ActiveValue::Set(0),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(0),
+            deleted_at: ActiveValue::Set(0),
+        };
+
+        let key = ApplicationKeyEntity::insert(new_key)
+            .exec(&application.state.database_core)
+            .await?;
+        let key_model = ApplicationKeyEntity::find_by_id(key.last_insert_id)
+            .one(&application.state.database_core)
+            .await?;
+
+        if let Some(record) = key_model {
+            Ok(ApplicationKey {
+                application: application.clone(),
+                record,
+            })
+        } else {
+            Err(anyhow!("Unable to create application key"))
+        }
+    }
+
+    /// get the newest active key that matches the supplied name
+    pub async fn get(
+        application: &Application<Extension>,
+        name: &str,
+    ) -> anyhow::Result<Option<ApplicationKey<Extension>>> {
+        let model = ApplicationKeyEntity::find()
+            .filter(
+                ApplicationKey::<Extension>::active_condition(application.record.id, unix_timestamp())
+                    .add(application_keys::Column::Name.eq(name)),
+            )
+            .order_by_desc(application_keys::Column::Id)
+            .one(&application.state.database_core)
+            .await?;
+
+        Ok(model.map(|record| ApplicationKey {
+            application: application.clone(),
+            record,
+        }))
+    }
+
+    /// list every key that is currently able to authenticate the application
+    pub async fn list(application: &Application<Extension>) -> anyhow::Result<Vec<ApplicationKey<Extension>>> {
+        let models = ApplicationKeyEntity::find()
+            .filter(ApplicationKey::<Extension>::active_condition(
+                application.record.id,
+                unix_timestamp(),
+            ))
+            .order_by_asc(application_keys::Column::Id)
+            .all(&application.state.database_core)
+            .await?;
+
+        Ok(models
+            .into_iter()
+            .map(|record| ApplicationKey {
+                application: application.clone(),
+                record,
+            })
+            .collect())
+    }
+
+    /// finds the active key tied to the application that matches the supplied secret
+    pub(crate) async fn find_by_secret(
+        application_id: i64,
+        secret: &str,
+        database: &DatabaseConnection,
+    ) -> anyhow::Result<Option<application_keys::Model>> {
+        let model = ApplicationKeyEntity::find()
+            .filter(
+                ApplicationKey::<Extension>::active_condition(application_id, unix_timestamp())
+                    .add(application_keys::Column::HashSecret.eq(secret)),
+            )
+            .one(database)
+            .await?;
+
+        Ok(model)
+    }
+
+    /// records that the key was just used to authenticate.
+    /// fired off into its own task since the caller should not have to wait on this
+    pub(crate) fn touch(record: &application_keys::Model, database: &DatabaseConnection) {
+        let mut active: application_keys::ActiveModel = record.clone().into();
+        active.last_used_at = ActiveValue::Set(unix_timestamp());
+        let database = database.clone();
+        tokio::spawn(async move {
+            if let Err(err) = active.update(&database).await {
+                tracing::warn!("Unable to update key last used timestamp: {}", err);
+            }
+        });
+    }
+
+    /// issue a replacement key with the same name and scopes.
+    /// this key will stay valid until the grace period runs out so running instances can pick up the new secret
+    pub async fn rotate(&mut self, grace: Duration) -> anyhow::Result<ApplicationKey<Extension>> {
+        let scopes = self.scopes();
+        let new_key =
+            ApplicationKey::create(&self.application, &self.record.name, &scopes, self.record.expires_at).await?;
+
+        let timestamp = unix_timestamp();
+        let grace_expires = timestamp + grace.as_secs() as i64;
+        if self.record.expires_at == 0 || self.record.expires_at > grace_expires {
+            self.record.expires_at = grace_expires;
+        }
+        self.record.updated_at = timestamp;
+
+        let mut active: application_keys::ActiveModel = self.record.clone().into();
+        active.expires_at = ActiveValue::Set(self.record.expires_at);
+        active.updated_at = ActiveValue::Set(timestamp);
+        active.update(&self.application.state.database_core).await?;
+        self.application.state.credentials.bump(&self.application.record.hash);
+
+        Ok(new_key)
+    }
+
+    /// immediately revoke the key
+    pub async fn revoke(&mut self) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        self.record.updated_at = timestamp;
+        self.record.deleted_at = timestamp;
+
+        let mut active: application_keys::ActiveModel = self.record.clone().into();
+        active.updated_at = ActiveValue::Set(timestamp);
+        active.deleted_at = ActiveValue::Set(timestamp);
+        active.update(&self.application.state.database_core).await?;
+        self.application.state.credentials.bump(&self.application.record.hash);
+
+        Ok(())
+    }
+
+    /// get the underlying key record
+    pub(crate) fn record(&self) -> &application_keys::Model {
+        &self.record
+    }
+
+    /// get the name of the key
+    pub fn name(&self) -> &str {
+        self.record.name.as_str()
+    }
+
+    /// get the secret that is used to authenticate with this key
+    pub fn secret(&self) -> &str {
+        self.record.hash_secret.as_str()
+    }
+
+    /// get the scopes that have been granted to this key
+    pub fn scopes(&self) -> Vec<&str> {
+        key_scopes(&self.record)
+    }
+
+    /// checks if the key has been granted the scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        key_has_scope(&self.record, scope)
+    }
+
+    /// unix timestamp of when the key expires. 0 means the key never expires
+    pub fn expires_at(&self) -> UnixTimestamp {
+        self.record.expires_at
+    }
+
+    /// unix timestamp of the last time the key was used to authenticate. 0 means never
+    pub fn last_used_at(&self) -> UnixTimestamp {
+        self.record.last_used_at
+    }
+
+    /// checks if the key is no longer able to authenticate
+    pub fn is_expired(&self) -> bool {
+        self.record.deleted_at > 0 || (self.record.expires_at > 0 && self.record.expires_at <= unix_timestamp())
+    }
+
+    fn active_condition(application_id: i64, timestamp: UnixTimestamp) -> Condition {
+        Condition::all()
+            .add(application_keys::Column::Application.eq(application_id))
+            .add(application_keys::Column::DeletedAt.eq(0))
+            .add(
+                Condition::any()
+                    .add(application_keys::Column::ExpiresAt.eq(0))
+                    .add(application_keys::Column::ExpiresAt.gt(timestamp)),
+            )
+    }
+}
+
+/// split the stored scopes of a key record
+pub(crate) fn key_scopes(record: &application_keys::Model) -> Vec<&str> {
+    record
+        .scopes
+        .split(',')
+        .map(|scope| scope.trim())
+        .filter(|scope| !scope.is_empty())
+        .collect()
+}
+
+/// checks if the key record has been granted the scope
+pub(crate) fn key_has_scope(record: &application_keys::Model, scope: &str) -> bool {
+    key_scopes(record)
+        .into_iter()
+        .any(|granted| granted == SCOPE_ALL || granted == scope)
+}
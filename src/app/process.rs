@@ -1,20 +1,143 @@
 use super::Application;
-use crate::entities::application_process_logs;
 use crate::{
+    alias::{RecordId, UnixTimestamp},
     entities::{self, application_processes},
     util::unix_timestamp,
 };
 use anyhow::anyhow;
 use application_processes::Entity as ApplicationProcessEntity;
-use sea_orm::{ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter};
-use tokio::task::JoinHandle;
+use layer::ProcessLogLayer;
+use level::LogLevels;
+use query::LogQuery;
+use record::LogRecord;
+use sea_orm::{ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use writer::LogHandle;
 
+pub mod alert;
+pub mod layer;
+pub mod level;
+pub mod query;
+pub mod record;
+pub mod retention;
+pub mod run;
+pub mod tail;
+pub mod writer;
+
+/// severity of a log entry, stored as its number in `application_process_logs.type`.
+/// lower numbers are more severe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(i8)]
 pub enum LogLevel {
+    Critical = -1,
     Error = 0,
+    #[serde(alias = "warn")]
     Warning = 1,
     Info = 2,
     Debug = 3,
+    Trace = 4,
+}
+
+impl LogLevel {
+    /// every level, from most to least severe
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Critical,
+        LogLevel::Error,
+        LogLevel::Warning,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Critical => "critical",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl From<LogLevel> for i8 {
+    fn from(level: LogLevel) -> Self {
+        level as i8
+    }
+}
+
+impl TryFrom<i8> for LogLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i8) -> Result<Self, anyhow::Error> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| *level as i8 == value)
+            .ok_or_else(|| anyhow!("{} is not a valid log level", value))
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    /// reads a level by name, case insensitive, or by its stored number
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        if value == "warn" {
+            return Ok(LogLevel::Warning);
+        }
+
+        match LogLevel::ALL.into_iter().find(|level| level.as_str() == value) {
+            Some(level) => Ok(level),
+            None => match value.parse::<i8>() {
+                Ok(number) => LogLevel::try_from(number),
+                Err(_) => Err(anyhow!("'{}' is not a valid log level", value)),
+            },
+        }
+    }
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warning,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        LogLevel::from(&level)
+    }
+}
+
+/// tracing has no critical level, those are sent as errors
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Critical | LogLevel::Error => Level::ERROR,
+            LogLevel::Warning => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -24,6 +147,10 @@ where
 {
     application: Application<Extension>,
     record: application_processes::Model,
+    /// the active run, shared between clones
+    run: Arc<Mutex<Option<RecordId>>>,
+    /// levels written and echoed, shared between clones
+    levels: Arc<LogLevels>,
 }
 
 impl<Extension> ApplicationProcess<Extension>
@@ -49,6 +176,8 @@ where
             return Ok(ApplicationProcess {
                 application: application.clone(),
                 record,
+                run: Arc::default(),
+                levels: Arc::default(),
             });
         }
 
@@ -81,32 +210,131 @@ where
             Ok(ApplicationProcess {
                 application: application.clone(),
                 record,
+                run: Arc::default(),
+                levels: Arc::default(),
             })
         } else {
             Err(anyhow!("Unable to get process"))
         }
     }
 
-    pub fn log_info(&self, content: &str) -> JoinHandle<()> {
+    /// list all processes that belong to the application
+    pub async fn list(application: &Application<Extension>) -> anyhow::Result<Vec<ApplicationProcess<Extension>>> {
+        let models = ApplicationProcessEntity::find()
+            .filter(
+                Condition::all()
+                    .add(application_processes::Column::Application.eq(application.record.id))
+                    .add(application_processes::Column::DeletedAt.eq(0)),
+            )
+            .order_by_asc(application_processes::Column::Name)
+            .all(&application.state.database_core)
+            .await?;
+
+        Ok(models
+            .into_iter()
+            .map(|record| ApplicationProcess {
+                application: application.clone(),
+                record,
+                run: Arc::default(),
+                levels: Arc::default(),
+            })
+            .collect())
+    }
+
+    /// get the record id of the process
+    pub fn id(&self) -> RecordId {
+        self.record.id
+    }
+
+    /// get the name of the process
+    pub fn name(&self) -> &str {
+        self.record.name.as_str()
+    }
+
+    /// get the identifying hash of the process
+    pub fn hash(&self) -> &str {
+        self.record.hash.as_str()
+    }
+
+    /// unix timestamp of when the process was first created
+    pub fn created_at(&self) -> UnixTimestamp {
+        self.record.created_at
+    }
+
+    /// a `tracing_subscriber` layer that writes tracing events into the logs of this process
+    pub fn layer(&self) -> ProcessLogLayer {
+        ProcessLogLayer::new(
+            self.application.log_writer().clone(),
+            self.application.record.id,
+            self.record.id,
+        )
+        .with_levels(self.levels.clone())
+    }
+
+    /// a query over the logs of this process
+    pub fn logs(&self) -> LogQuery {
+        LogQuery::new(self.application.record.id).with_process(self.record.id)
+    }
+
+    #[track_caller]
+    pub fn log_info(&self, content: &str) -> LogHandle {
         self.log(LogLevel::Info, content, None)
     }
 
-    pub fn log_warning(&self, content: &str) -> JoinHandle<()> {
+    #[track_caller]
+    pub fn log_warning(&self, content: &str) -> LogHandle {
         self.log(LogLevel::Warning, content, None)
     }
-    pub fn log_error(&self, content: &str) -> JoinHandle<()> {
+
+    #[track_caller]
+    pub fn log_error(&self, content: &str) -> LogHandle {
         self.log(LogLevel::Error, content, None)
     }
 
-    pub fn log_debug(&self, content: &str) -> JoinHandle<()> {
+    #[track_caller]
+    pub fn log_critical(&self, content: &str) -> LogHandle {
+        self.log(LogLevel::Critical, content, None)
+    }
+
+    #[track_caller]
+    pub fn log_debug(&self, content: &str) -> LogHandle {
         self.log(LogLevel::Debug, content, None)
     }
 
-    /// log a message to our database
-    pub fn log(&self, log_level: LogLevel, content: &str, sub_id: Option<&str>) -> JoinHandle<()> {
-        let sub_id = sub_id.unwrap_or("").to_string();
-        match log_level {
-            LogLevel::Error => {
+    #[track_caller]
+    pub fn log_trace(&self, content: &str) -> LogHandle {
+        self.log(LogLevel::Trace, content, None)
+    }
+
+    /// log a message to our database. The caller is recorded as the source of the entry
+    #[track_caller]
+    pub fn log(&self, log_level: LogLevel, content: &str, sub_id: Option<&str>) -> LogHandle {
+        self.write(LogRecord::new(log_level, content).with_sub_id(sub_id.unwrap_or("")))
+    }
+
+    /// write a structured entry to our database.
+    /// the entry is buffered by the log writer of the application and written in a batch with other entries.
+    /// the message is also sent to `tracing`, a `ProcessLogLayer` skips it so it is not written twice.
+    /// entries below the levels of the process are neither echoed nor written
+    pub fn write(&self, record: LogRecord) -> LogHandle {
+        let level = *record.level();
+        if self.levels.echoes(&level) {
+            self.echo(&record);
+        }
+
+        if !self.levels.persists(&level) {
+            return LogHandle::skipped();
+        }
+
+        let new_log = record.into_model(self.application.record.id, self.record.id);
+        self.application.log_writer().write(new_log)
+    }
+
+    fn echo(&self, record: &LogRecord) {
+        let sub_id = record.sub_id();
+        let content = record.content();
+        match record.level() {
+            LogLevel::Critical | LogLevel::Error => {
                 tracing::error!("{sub_id}\r\n{content}");
             }
             LogLevel::Warning => {
@@ -118,36 +346,38 @@ where
             LogLevel::Debug => {
                 tracing::debug!("{sub_id}\r\n{content}");
             }
+            LogLevel::Trace => {
+                tracing::trace!("{sub_id}\r\n{content}");
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn log_level_test() -> anyhow::Result<()> {
+        // stored numbers of the original levels must not move
+        assert_eq!(i8::from(LogLevel::Error), 0);
+        assert_eq!(i8::from(LogLevel::Debug), 3);
+        for level in LogLevel::ALL {
+            assert_eq!(LogLevel::try_from(i8::from(level))?, level);
+            assert_eq!(level.to_string().parse::<LogLevel>()?, level);
+        }
+        assert!(LogLevel::try_from(5).is_err());
+
+        assert_eq!(" Warn ".parse::<LogLevel>()?, LogLevel::Warning);
+        assert_eq!("-1".parse::<LogLevel>()?, LogLevel::Critical);
+        assert!("loud".parse::<LogLevel>().is_err());
+
+        assert_eq!(serde_json::to_string(&LogLevel::Critical)?, r#""critical""#);
+        assert_eq!(serde_json::from_str::<LogLevel>(r#""warn""#)?, LogLevel::Warning);
 
-        let log_type = log_level as i8;
-        let application_id = self.application.record.id;
-        let process_id = self.record.id;
-        let content_cloned = content.to_string();
-        let database = self.application.state.database_core.clone();
-
-        // spawn a background task to log this off to a database.
-        // we do this so we dont have to wait around for the response
-        tokio::spawn(async move {
-            let timestamp = unix_timestamp();
-            let seed = format!("{}||{}||{}||{}", timestamp, application_id, process_id, content_cloned);
-            let hash = format!("{:x}", md5::compute(seed));
-            let hash_sub = format!("{:x}", md5::compute(sub_id));
-            let new_log = application_process_logs::ActiveModel {
-                id: ActiveValue::NotSet,
-                application: ActiveValue::Set(application_id),
-                process: ActiveValue::Set(process_id),
-                hash: ActiveValue::Set(hash),
-                hash_sub: ActiveValue::Set(hash_sub),
-                r#type: ActiveValue::Set(log_type),
-                content: ActiveValue::Set(content_cloned),
-                created_at: ActiveValue::Set(timestamp),
-                updated_at: ActiveValue::Set(0),
-                deleted_at: ActiveValue::Set(0),
-            };
-
-            // quite literally don't care about the errors
-            let _ = application_process_logs::Entity::insert(new_log).exec(&database).await;
-        })
+        assert_eq!(LogLevel::from(Level::TRACE), LogLevel::Trace);
+        assert_eq!(Level::from(LogLevel::Critical), Level::ERROR);
+        assert_eq!(Level::from(LogLevel::Debug), Level::DEBUG);
+        Ok(())
     }
 }
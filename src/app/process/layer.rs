@@ -0,0 +1,281 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::level::LogLevels;
+use super::record::LogRecord;
+use super::writer::LogWriter;
+use super::LogLevel;
+use crate::alias::RecordId;
+
+/// targets that are never written. Our own logging and the database drivers would otherwise feed every write back into the writer
+const IGNORED_TARGETS: [&str; 3] = ["levelcrush::app::process", "sqlx", "sea_orm"];
+
+/// field that supplies the sub id of an event when no other field has been configured
+pub const DEFAULT_SUB_FIELD: &str = "sub_id";
+
+/// field that supplies the correlation id of an event
+pub const CORRELATION_FIELD: &str = "correlation_id";
+
+/// where the sub id (`hash_sub`) of a persisted event comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubIdSource {
+    /// the value of this field, falling back to the name of the span the event happened in
+    Field(String),
+    /// the name of the span the event happened in
+    Span,
+}
+
+/// a `tracing_subscriber::Layer` that writes tracing events into the logs of a process through the log writer.
+/// created through `ApplicationProcess::layer`
+#[derive(Clone)]
+pub struct ProcessLogLayer {
+    writer: LogWriter,
+    application: RecordId,
+    process: RecordId,
+    level: Level,
+    targets: Vec<String>,
+    sub_id: SubIdSource,
+    levels: Option<Arc<LogLevels>>,
+}
+
+impl ProcessLogLayer {
+    pub fn new(writer: LogWriter, application: RecordId, process: RecordId) -> ProcessLogLayer {
+        ProcessLogLayer {
+            writer,
+            application,
+            process,
+            level: Level::INFO,
+            targets: Vec::new(),
+            sub_id: SubIdSource::Field(DEFAULT_SUB_FIELD.to_string()),
+            levels: None,
+        }
+    }
+
+    /// the most verbose level that is written. Defaults to info
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// only write events whose target starts with the prefix. Can be called more then once.
+    /// without any targets every event is written
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.targets.push(target.to_string());
+        self
+    }
+
+    pub fn with_sub_id(mut self, source: SubIdSource) -> Self {
+        self.sub_id = source;
+        self
+    }
+
+    /// also skip events below the persisted level of a process, following it as it changes
+    pub fn with_levels(mut self, levels: Arc<LogLevels>) -> Self {
+        self.levels = Some(levels);
+        self
+    }
+
+    /// checks if an event with this target and level should be written
+    fn enabled(&self, target: &str, level: &Level) -> bool {
+        if IGNORED_TARGETS.iter().any(|ignored| target.starts_with(ignored)) {
+            return false;
+        }
+
+        // more verbose levels compare as greater
+        if *level > self.level {
+            return false;
+        }
+
+        if !self
+            .levels
+            .as_ref()
+            .is_none_or(|levels| levels.persists(&LogLevel::from(level)))
+        {
+            return false;
+        }
+
+        self.targets.is_empty() || self.targets.iter().any(|prefix| target.starts_with(prefix.as_str()))
+    }
+}
+
+/// collects the message and the remaining fields of an event
+#[derive(Default)]
+struct EventVisitor {
+    sub_field: Option<String>,
+    sub_id: Option<String>,
+    correlation_id: Option<String>,
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl EventVisitor {
+    fn record(&mut self, field: &Field, value: Value) {
+        let name = field.name();
+        if name == "message" {
+            self.message = as_text(value);
+        } else if self.sub_field.as_deref() == Some(name) {
+            self.sub_id = Some(as_text(value));
+        } else if name == CORRELATION_FIELD {
+            self.correlation_id = Some(as_text(value));
+        } else {
+            self.fields.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// strings are used as is, anything else as its json representation
+fn as_text(value: Value) -> String {
+    match value {
+        Value::String(value) => value,
+        value => value.to_string(),
+    }
+}
+
+impl Visit for EventVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value).into());
+    }
+}
+
+impl<S> Layer<S> for ProcessLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !self.enabled(metadata.target(), metadata.level()) {
+            return;
+        }
+
+        let mut visitor = EventVisitor {
+            sub_field: match &self.sub_id {
+                SubIdSource::Field(field) => Some(field.clone()),
+                SubIdSource::Span => None,
+            },
+            ..Default::default()
+        };
+        event.record(&mut visitor);
+
+        let sub_id = visitor
+            .sub_id
+            .clone()
+            .or_else(|| ctx.event_span(event).map(|span| span.name().to_string()))
+            .unwrap_or_default();
+
+        let mut record = LogRecord::new(metadata.level().into(), &visitor.message)
+            .with_sub_id(&sub_id)
+            .with_correlation_id(visitor.correlation_id.as_deref().unwrap_or(""))
+            // the same source file `LogRecord::new` records, so entries from both line up
+            .with_module(metadata.file().or(metadata.module_path()).unwrap_or(metadata.target()))
+            .with_line(metadata.line().unwrap_or(0));
+        for (name, value) in visitor.fields {
+            record = record.with_field(&name, value);
+        }
+
+        let model = record.into_model(self.application, self.process);
+        // nobody is around to wait on the handle of an event
+        self.writer.write(model);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use sea_orm::DatabaseConnection;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::app::process::writer::LogWriterConfig;
+
+    /// records the last event with the visitor of the layer
+    struct CaptureLayer(Arc<Mutex<EventVisitor>>);
+
+    impl<S: Subscriber> Layer<S> for CaptureLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let mut visitor = EventVisitor {
+                sub_field: Some(DEFAULT_SUB_FIELD.to_string()),
+                ..Default::default()
+            };
+            event.record(&mut visitor);
+            *self.0.lock().expect("Capture lock poisoned") = visitor;
+        }
+    }
+
+    #[tokio::test]
+    pub async fn process_log_layer_test() {
+        let writer = LogWriter::new(
+            DatabaseConnection::Disconnected,
+            LogWriterConfig {
+                interval: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+        let layer = ProcessLogLayer::new(writer.clone(), 1, 1)
+            .with_level(Level::DEBUG)
+            .with_target("levelcrush");
+
+        assert!(layer.enabled("levelcrush::server", &Level::DEBUG));
+        assert!(!layer.enabled("levelcrush::server", &Level::TRACE));
+        assert!(!layer.enabled("levelcrush::app::process::writer", &Level::ERROR));
+        assert!(!layer.enabled("sqlx::query", &Level::INFO));
+        assert!(!layer.enabled("hyper", &Level::INFO));
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "levelcrush::mock", user = 42, "joined");
+            tracing::info!(target: "hyper", "ignored");
+        });
+        assert_eq!(writer.pending(), 1);
+
+        let captured = Arc::new(Mutex::new(EventVisitor::default()));
+        let subscriber = tracing_subscriber::registry().with(CaptureLayer(captured.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                user = 42,
+                admin = true,
+                correlation_id = "req-1",
+                sub_id = "guild-1",
+                "joined"
+            );
+        });
+
+        let visitor = captured.lock().expect("Capture lock poisoned");
+        assert_eq!(visitor.message, "joined");
+        assert_eq!(visitor.sub_id.as_deref(), Some("guild-1"));
+        assert_eq!(visitor.correlation_id.as_deref(), Some("req-1"));
+        assert_eq!(
+            Value::Object(visitor.fields.clone()).to_string(),
+            r#"{"admin":true,"user":42}"#
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::atomic::{AtomicI8, Ordering};
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+use super::{ApplicationProcess, LogLevel};
+use crate::app::settings::schema::{SettingSchema, SettingValueType};
+use crate::app::settings::{ApplicationSettings, SharedApplicationSettings};
+
+/// every level is written and echoed until a setting says otherwise
+pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Trace;
+
+/// the most verbose level written to the database
+pub fn log_level_setting(process: &str) -> String {
+    format!("process.{}.log_level", process)
+}
+
+/// the most verbose level echoed to `tracing`
+pub fn console_level_setting(process: &str) -> String {
+    format!("process.{}.console_level", process)
+}
+
+/// minimum levels of a process, shared between every clone of it and changeable while it runs
+#[derive(Debug)]
+pub struct LogLevels {
+    persist: AtomicI8,
+    console: AtomicI8,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        LogLevels {
+            persist: AtomicI8::new(DEFAULT_LOG_LEVEL as i8),
+            console: AtomicI8::new(DEFAULT_LOG_LEVEL as i8),
+        }
+    }
+}
+
+impl LogLevels {
+    /// the most verbose level written to the database
+    pub fn persist(&self) -> i8 {
+        self.persist.load(Ordering::Relaxed)
+    }
+
+    /// the most verbose level echoed to `tracing`
+    pub fn console(&self) -> i8 {
+        self.console.load(Ordering::Relaxed)
+    }
+
+    pub fn set_persist(&self, level: LogLevel) {
+        self.persist.store(level as i8, Ordering::Relaxed);
+    }
+
+    pub fn set_console(&self, level: LogLevel) {
+        self.console.store(level as i8, Ordering::Relaxed);
+    }
+
+    /// checks if an entry of this level is written to the database
+    pub fn persists(&self, level: &LogLevel) -> bool {
+        (*level as i8) <= self.persist()
+    }
+
+    /// checks if an entry of this level is echoed to `tracing`
+    pub fn echoes(&self, level: &LogLevel) -> bool {
+        (*level as i8) <= self.console()
+    }
+
+    /// read both levels of the process from the global settings. Missing or invalid values fall back to the default
+    pub fn load<Extension>(&self, settings: &ApplicationSettings<Extension>, process: &str)
+    where
+        Extension: Clone,
+    {
+        let read = |name: String| match settings.get_global(&name) {
+            Some(value) => value.parse::<LogLevel>().map(i8::from).unwrap_or_else(|_| {
+                tracing::warn!("Ignoring invalid log level '{}' in setting {}", value, name);
+                DEFAULT_LOG_LEVEL as i8
+            }),
+            None => DEFAULT_LOG_LEVEL as i8,
+        };
+
+        self.persist.store(read(log_level_setting(process)), Ordering::Relaxed);
+        self.console
+            .store(read(console_level_setting(process)), Ordering::Relaxed);
+    }
+}
+
+impl<Extension> ApplicationProcess<Extension>
+where
+    Extension: Clone,
+{
+    /// the levels this process writes and echoes at
+    pub fn log_levels(&self) -> &LogLevels {
+        &self.levels
+    }
+
+    /// apply the level settings of this process once. Use `watch_log_levels` to follow changes
+    pub fn load_log_levels(&self, settings: &ApplicationSettings<Extension>) {
+        self.levels.load(settings, self.name());
+    }
+
+    /// register the level settings of this process so they show up with their valid options
+    pub async fn register_log_levels(&self, settings: &mut ApplicationSettings<Extension>) -> anyhow::Result<()> {
+        let options = SettingValueType::Enum(LogLevel::ALL.iter().map(|level| level.to_string()).collect());
+        settings
+            .register(
+                SettingSchema::new(&log_level_setting(self.name()), options.clone())
+                    .with_default(DEFAULT_LOG_LEVEL.as_str())
+                    .with_description("most verbose level written to the database"),
+            )
+            .await?;
+        settings
+            .register(
+                SettingSchema::new(&console_level_setting(self.name()), options)
+                    .with_default(DEFAULT_LOG_LEVEL.as_str())
+                    .with_description("most verbose level echoed to the console"),
+            )
+            .await
+    }
+
+    /// apply the level settings of this process now and again every time they change.
+    /// changes arrive through the shared settings, so they need to be refreshed or on a transport to be seen.
+    /// stops once the shared settings are dropped
+    pub async fn watch_log_levels(&self, settings: &SharedApplicationSettings<Extension>) -> JoinHandle<()>
+    where
+        Extension: Send + Sync + 'static,
+    {
+        let name = self.name().to_string();
+        let levels = self.levels.clone();
+        let mut subscription = {
+            let settings = settings.read().await;
+            levels.load(&settings, &name);
+            settings.subscribe(&format!("process.{}.", name))
+        };
+
+        // holding on to the settings would keep their refresh task alive forever
+        let weak = Arc::downgrade(settings);
+        tokio::spawn(async move {
+            while subscription.recv().await.is_some() {
+                let settings = match weak.upgrade() {
+                    Some(settings) => settings,
+                    None => break,
+                };
+                levels.load(&*settings.read().await, &name);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn log_levels_test() {
+        let levels = LogLevels::default();
+        assert!(levels.persists(&LogLevel::Trace));
+
+        levels.set_persist(LogLevel::Warning);
+        levels.set_console(LogLevel::Error);
+        assert!(levels.persists(&LogLevel::Error));
+        assert!(levels.persists(&LogLevel::Warning));
+        assert!(!levels.persists(&LogLevel::Info));
+        assert!(!levels.echoes(&LogLevel::Warning));
+        assert_eq!(log_level_setting("worker"), "process.worker.log_level");
+    }
+}
@@ -0,0 +1,340 @@
+use sea_orm::sea_query::{Expr, LikeExpr};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+
+use super::LogLevel;
+use crate::alias::{RecordId, UnixTimestamp};
+use crate::entities::application_process_logs;
+use crate::server::PaginationData;
+
+/// page size used when none is given
+pub const DEFAULT_LIMIT: u64 = 50;
+
+/// largest page that can be requested
+pub const MAX_LIMIT: u64 = 1000;
+
+/// a single log entry as returned by a `LogQuery`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProcessLog {
+    pub id: RecordId,
+    pub process: RecordId,
+    pub level: i8,
+    pub content: String,
+    pub fields: Option<serde_json::Value>,
+    pub sub_id: String,
+    pub correlation_id: String,
+    pub module: String,
+    pub line: i32,
+    pub created_at: UnixTimestamp,
+}
+
+impl From<application_process_logs::Model> for ProcessLog {
+    fn from(model: application_process_logs::Model) -> Self {
+        ProcessLog {
+            id: model.id,
+            process: model.process,
+            level: model.r#type,
+            content: model.content,
+            // fields are only ever written as json, anything else is left out instead of failing the page
+            fields: model.fields.and_then(|fields| serde_json::from_str(&fields).ok()),
+            sub_id: model.sub_id,
+            correlation_id: model.correlation_id,
+            module: model.module,
+            line: model.line,
+            created_at: model.created_at,
+        }
+    }
+}
+
+impl ProcessLog {
+    /// the stored level as a `LogLevel`. Fails for numbers no level uses
+    pub fn log_level(&self) -> anyhow::Result<LogLevel> {
+        LogLevel::try_from(self.level)
+    }
+}
+
+/// a page of log entries. Shaped like `PaginationResponse` with the cursor of the next page next to it
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LogPage {
+    pub data: Vec<ProcessLog>,
+    pub pagination: PaginationData,
+    /// pass to `LogQuery::with_cursor` to get the next page. None when this is the last page
+    pub cursor: Option<RecordId>,
+}
+
+/// builds a query over the logs of an application. Entries are returned newest first
+#[derive(Clone, Debug, Default)]
+pub struct LogQuery {
+    application: RecordId,
+    processes: Vec<RecordId>,
+    levels: Vec<i8>,
+    severity: Option<i8>,
+    sub_id: Option<String>,
+    correlation_id: Option<String>,
+    from: Option<UnixTimestamp>,
+    to: Option<UnixTimestamp>,
+    search: Option<String>,
+    after: Option<RecordId>,
+    cursor: Option<RecordId>,
+    page: u64,
+    totals: bool,
+    limit: u64,
+}
+
+impl LogQuery {
+    pub fn new(application: RecordId) -> LogQuery {
+        LogQuery {
+            application,
+            page: 1,
+            limit: DEFAULT_LIMIT,
+            ..Default::default()
+        }
+    }
+
+    /// only entries of this process. Can be called more then once
+    pub fn with_process(mut self, process: RecordId) -> Self {
+        self.processes.push(process);
+        self
+    }
+
+    /// only entries of exactly this level. Can be called more then once
+    pub fn with_level(mut self, level: LogLevel) -> Self {
+        self.levels.push(level as i8);
+        self
+    }
+
+    /// only entries that are at least as severe as the level. Warning includes errors, but not info
+    pub fn with_severity(mut self, level: LogLevel) -> Self {
+        self.severity = Some(level as i8);
+        self
+    }
+
+    pub fn with_sub_id(mut self, sub_id: &str) -> Self {
+        self.sub_id = Some(sub_id.to_string());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+
+    /// only entries created within the range. Both ends are inclusive and either can be left open
+    pub fn with_range(mut self, from: Option<UnixTimestamp>, to: Option<UnixTimestamp>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// only entries whose content or fields contain the term
+    pub fn with_search(mut self, term: &str) -> Self {
+        self.search = Some(term.to_string()).filter(|term| !term.is_empty());
+        self
+    }
+
+    /// only entries written after the entry with this id. Useful for following new entries
+    pub fn with_after(mut self, id: RecordId) -> Self {
+        self.after = Some(id);
+        self
+    }
+
+    /// continue from the cursor of a previous page
+    pub fn with_cursor(mut self, cursor: RecordId) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// the page number reported in the pagination. Callers following cursors know which page they are on
+    pub fn with_page(mut self, page: u64) -> Self {
+        self.page = page.max(1);
+        self
+    }
+
+    /// count everything that matches so the pagination has totals. Off by default since it is an extra query per page
+    pub fn with_totals(mut self) -> Self {
+        self.totals = true;
+        self
+    }
+
+    /// entries per page, between 1 and `MAX_LIMIT`
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    fn limit(&self) -> u64 {
+        self.limit.clamp(1, MAX_LIMIT)
+    }
+
+    /// every filter except the cursor
+    fn condition(&self) -> Condition {
+        let mut condition = Condition::all()
+            .add(application_process_logs::Column::Application.eq(self.application))
+            .add(application_process_logs::Column::DeletedAt.eq(0));
+
+        if !self.processes.is_empty() {
+            condition = condition.add(application_process_logs::Column::Process.is_in(self.processes.clone()));
+        }
+
+        if !self.levels.is_empty() {
+            condition = condition.add(application_process_logs::Column::Type.is_in(self.levels.clone()));
+        }
+
+        // more severe levels have lower values
+        if let Some(severity) = self.severity {
+            condition = condition.add(application_process_logs::Column::Type.lte(severity));
+        }
+
+        // entries written before the sub id column existed only have the hash, so match on that
+        if let Some(sub_id) = self.sub_id.as_ref() {
+            let hash_sub = format!("{:x}", md5::compute(sub_id));
+            condition = condition.add(application_process_logs::Column::HashSub.eq(hash_sub));
+        }
+
+        if let Some(correlation_id) = self.correlation_id.as_ref() {
+            condition = condition.add(application_process_logs::Column::CorrelationId.eq(correlation_id.as_str()));
+        }
+
+        if let Some(from) = self.from {
+            condition = condition.add(application_process_logs::Column::CreatedAt.gte(from));
+        }
+
+        if let Some(to) = self.to {
+            condition = condition.add(application_process_logs::Column::CreatedAt.lte(to));
+        }
+
+        // the term is matched literally, wildcards in it are escaped
+        if let Some(term) = self.search.as_ref() {
+            let pattern = like_pattern(term);
+            condition = condition.add(
+                Condition::any()
+                    .add(
+                        Expr::col((
+                            application_process_logs::Entity,
+                            application_process_logs::Column::Content,
+                        ))
+                        .like(LikeExpr::new(pattern.clone()).escape('\\')),
+                    )
+                    .add(
+                        Expr::col((
+                            application_process_logs::Entity,
+                            application_process_logs::Column::Fields,
+                        ))
+                        .like(LikeExpr::new(pattern).escape('\\')),
+                    ),
+            );
+        }
+
+        if let Some(after) = self.after {
+            condition = condition.add(application_process_logs::Column::Id.gt(after));
+        }
+
+        condition
+    }
+
+    /// fetch a single page. Totals are only counted when asked for through `with_totals`
+    pub async fn fetch(&self, database: &DatabaseConnection) -> anyhow::Result<LogPage> {
+        let limit = self.limit();
+        let condition = self.condition();
+
+        let mut page_condition = condition.clone();
+        if let Some(cursor) = self.cursor {
+            page_condition = page_condition.add(application_process_logs::Column::Id.lt(cursor));
+        }
+
+        // one extra entry tells us if there is another page without counting again
+        let mut logs = application_process_logs::Entity::find()
+            .filter(page_condition)
+            .order_by_desc(application_process_logs::Column::Id)
+            .limit(limit + 1)
+            .all(database)
+            .await?;
+
+        let has_more = logs.len() as u64 > limit;
+        logs.truncate(limit as usize);
+
+        let total_results = match self.totals {
+            true => Some(
+                application_process_logs::Entity::find()
+                    .filter(condition)
+                    .count(database)
+                    .await?,
+            ),
+            false => None,
+        };
+
+        let cursor = match has_more {
+            true => logs.last().map(|log| log.id),
+            false => None,
+        };
+        let data = logs.into_iter().map(ProcessLog::from).collect::<Vec<_>>();
+
+        Ok(LogPage {
+            pagination: pagination(total_results, self.page, limit, data.len(), self.search.as_deref()),
+            data,
+            cursor,
+        })
+    }
+}
+
+/// a LIKE pattern that matches the term anywhere, with `%`, `_` and the escape character itself escaped
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// page numbers start at 1, the same as the rest of our paginated responses.
+/// totals are left at 0 when they were not counted
+fn pagination(total_results: Option<u64>, page: u64, limit: u64, showing: usize, term: Option<&str>) -> PaginationData {
+    let total_results = total_results.unwrap_or_default();
+    PaginationData {
+        total_results: total_results as u32,
+        total_pages: total_results.div_ceil(limit) as u32,
+        page: page as u32,
+        limit: limit as u32,
+        showing,
+        term: term.unwrap_or_default().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn log_pagination_test() {
+        let page = pagination(Some(120), 2, 50, 50, Some("timeout"));
+        assert_eq!(page.total_pages, 3);
+        assert_eq!(page.page, 2);
+        assert_eq!(page.term, "timeout");
+
+        let page = pagination(None, 1, 50, 0, None);
+        assert_eq!(page.total_results, 0);
+        assert_eq!(page.total_pages, 0);
+        assert_eq!(page.page, 1);
+
+        assert_eq!(like_pattern("timeout"), "%timeout%");
+        assert_eq!(like_pattern("100%_done\\"), "%100\\%\\_done\\\\%");
+
+        assert_eq!(LogQuery::new(1).with_limit(0).limit(), 1);
+        assert_eq!(LogQuery::new(1).with_limit(5000).limit(), MAX_LIMIT);
+
+        let model = application_process_logs::Model {
+            id: 7,
+            fields: Some(r#"{"ms":1200}"#.to_string()),
+            ..Default::default()
+        };
+        let log = ProcessLog::from(model);
+        assert_eq!(log.fields, Some(serde_json::json!({ "ms": 1200 })));
+    }
+}
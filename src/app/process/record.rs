@@ -0,0 +1,152 @@
+use std::panic::Location;
+
+use sea_orm::ActiveValue;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::LogLevel;
+use crate::{alias::RecordId, entities::application_process_logs, util::unix_timestamp};
+
+/// a single structured log entry. Written through `ApplicationProcess::write`
+///
+/// the module and line default to the source file and line the record was created at
+pub struct LogRecord {
+    level: LogLevel,
+    content: String,
+    sub_id: String,
+    correlation_id: String,
+    fields: Map<String, Value>,
+    module: String,
+    line: u32,
+}
+
+impl LogRecord {
+    #[track_caller]
+    pub fn new(level: LogLevel, content: &str) -> LogRecord {
+        let location = Location::caller();
+        LogRecord {
+            level,
+            content: content.to_string(),
+            sub_id: String::new(),
+            correlation_id: String::new(),
+            fields: Map::new(),
+            module: location.file().to_string(),
+            line: location.line(),
+        }
+    }
+
+    /// attach a structured value. Setting the same field twice keeps the last value
+    pub fn with_field<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn with_sub_id(mut self, sub_id: &str) -> Self {
+        self.sub_id = sub_id.to_string();
+        self
+    }
+
+    /// ties entries of the same request or job together
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = correlation_id.to_string();
+        self
+    }
+
+    /// override where the entry came from. Should be a source file like `file!()` so it matches every other entry
+    pub fn with_module(mut self, module: &str) -> Self {
+        self.module = module.to_string();
+        self
+    }
+
+    pub fn with_line(mut self, line: u32) -> Self {
+        self.line = line;
+        self
+    }
+
+    pub fn level(&self) -> &LogLevel {
+        &self.level
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn sub_id(&self) -> &str {
+        &self.sub_id
+    }
+
+    pub fn correlation_id(&self) -> &str {
+        &self.correlation_id
+    }
+
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// the row that is handed to the log writer
+    pub(crate) fn into_model(
+        self,
+        application_id: RecordId,
+        process_id: RecordId,
+    ) -> application_process_logs::ActiveModel {
+        let timestamp = unix_timestamp();
+        // entries are inserted together, so identical lines in the same second still need their own hash
+        let seed = format!(
+            "{}||{}||{}||{}||{}",
+            timestamp,
+            application_id,
+            process_id,
+            self.content,
+            Uuid::new_v4()
+        );
+        let hash = format!("{:x}", md5::compute(seed));
+        // kept so existing queries on the hashed sub id keep working
+        let hash_sub = format!("{:x}", md5::compute(&self.sub_id));
+        let fields = match self.fields.is_empty() {
+            true => None,
+            false => Some(Value::Object(self.fields).to_string()),
+        };
+
+        application_process_logs::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(application_id),
+            process: ActiveValue::Set(process_id),
+            hash: ActiveValue::Set(hash),
+            hash_sub: ActiveValue::Set(hash_sub),
+            r#type: ActiveValue::Set(self.level as i8),
+            content: ActiveValue::Set(self.content),
+            fields: ActiveValue::Set(fields),
+            sub_id: ActiveValue::Set(self.sub_id),
+            correlation_id: ActiveValue::Set(self.correlation_id),
+            module: ActiveValue::Set(self.module),
+            line: ActiveValue::Set(self.line as i32),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(0),
+            deleted_at: ActiveValue::Set(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn log_record_test() {
+        let line = line!() + 1;
+        let record = LogRecord::new(LogLevel::Warning, "slow query")
+            .with_field("ms", 1200)
+            .with_field("table", "users")
+            .with_sub_id("guild-1")
+            .with_correlation_id("req-1");
+
+        let model = record.into_model(1, 2);
+        assert_eq!(model.module, ActiveValue::Set(file!().to_string()));
+        assert_eq!(model.line, ActiveValue::Set(line as i32));
+        assert_eq!(model.sub_id, ActiveValue::Set("guild-1".to_string()));
+        assert_eq!(
+            model.fields,
+            ActiveValue::Set(Some(r#"{"ms":1200,"table":"users"}"#.to_string()))
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tokio::task::JoinHandle;
+
+use super::query::ProcessLog;
+use super::LogLevel;
+use crate::alias::RecordId;
+use crate::app::Application;
+use crate::entities::{application_log_retention, application_process_logs};
+use crate::util::unix_timestamp;
+
+/// how long logs are kept and if they are archived before they are deleted.
+/// the most specific rule that matches a log entry wins. Rules for a process take priority over rules for a level
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetentionRule {
+    /// None applies the rule to every process of the application
+    pub process: Option<RecordId>,
+    /// None applies the rule to every level
+    pub level: Option<i8>,
+    pub keep: Duration,
+    /// write expired entries to the archive directory before deleting them
+    pub archive: bool,
+}
+
+impl RetentionRule {
+    fn priority(&self) -> u8 {
+        (self.process.is_some() as u8) * 2 + self.level.is_some() as u8
+    }
+
+    /// checks if both rules can match the same log entry
+    fn overlaps(&self, other: &RetentionRule) -> bool {
+        let process = self.process.is_none() || other.process.is_none() || self.process == other.process;
+        let level = self.level.is_none() || other.level.is_none() || self.level == other.level;
+        process && level
+    }
+
+    /// log entries this rule applies to, regardless of age
+    fn scope(&self) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(process) = self.process {
+            condition = condition.add(application_process_logs::Column::Process.eq(process));
+        }
+        if let Some(level) = self.level {
+            condition = condition.add(application_process_logs::Column::Type.eq(level));
+        }
+        condition
+    }
+
+    /// log entries this rule applies to, leaving out the entries that a rule with a higher priority applies to
+    fn condition(&self, rules: &[RetentionRule]) -> Condition {
+        let mut condition = self.scope();
+        for rule in rules.iter() {
+            if rule.priority() > self.priority() && self.overlaps(rule) {
+                condition = condition.add(rule.scope().not());
+            }
+        }
+        condition
+    }
+}
+
+impl From<application_log_retention::Model> for RetentionRule {
+    fn from(model: application_log_retention::Model) -> Self {
+        RetentionRule {
+            process: Some(model.process).filter(|process| *process > 0),
+            level: model.level,
+            keep: Duration::from_secs(model.keep_seconds.max(0) as u64),
+            archive: model.archive,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PurgeConfig {
+    /// most entries deleted in a single statement
+    pub batch_size: u64,
+    /// where archived entries are written. Rules that archive are skipped when this is not set
+    pub archive_dir: Option<PathBuf>,
+}
+
+impl Default for PurgeConfig {
+    fn default() -> Self {
+        PurgeConfig {
+            batch_size: 1000,
+            archive_dir: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub deleted: u64,
+    pub archived: u64,
+    /// archive files written during the purge
+    pub files: Vec<PathBuf>,
+}
+
+fn rule_hash(application: RecordId, process: Option<RecordId>, level: Option<i8>) -> String {
+    let level = level.map_or("*".to_string(), |level| level.to_string());
+    let seed = format!("{}||{}||{}", application, process.unwrap_or(0), level);
+    format!("{:x}", md5::compute(seed))
+}
+
+impl<Extension> Application<Extension>
+where
+    Extension: Clone,
+{
+    /// keep logs of the process and level for the duration. None for either applies the rule to every process or level
+    pub async fn set_log_retention(
+        &self,
+        process: Option<RecordId>,
+        level: Option<LogLevel>,
+        keep: Duration,
+        archive: bool,
+    ) -> anyhow::Result<()> {
+        let level = level.map(|level| level as i8);
+        let timestamp = unix_timestamp();
+        let active = application_log_retention::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(self.record.id),
+            hash: ActiveValue::Set(rule_hash(self.record.id, process, level)),
+            process: ActiveValue::Set(process.unwrap_or(0)),
+            level: ActiveValue::Set(level),
+            keep_seconds: ActiveValue::Set(keep.as_secs() as i64),
+            archive: ActiveValue::Set(archive),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(timestamp),
+            deleted_at: ActiveValue::Set(0),
+        };
+
+        application_log_retention::Entity::insert(active)
+            .on_conflict(
+                OnConflict::column(application_log_retention::Column::Hash)
+                    .update_columns([
+                        application_log_retention::Column::KeepSeconds,
+                        application_log_retention::Column::Archive,
+                        application_log_retention::Column::UpdatedAt,
+                        application_log_retention::Column::DeletedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.state.database_core)
+            .await?;
+
+        Ok(())
+    }
+
+    /// remove a retention rule. Logs it applied to fall back to the next matching rule, or are kept forever
+    pub async fn remove_log_retention(&self, process: Option<RecordId>, level: Option<LogLevel>) -> anyhow::Result<()> {
+        let hash = rule_hash(self.record.id, process, level.map(|level| level as i8));
+        let timestamp = unix_timestamp();
+        application_log_retention::Entity::update_many()
+            .col_expr(application_log_retention::Column::DeletedAt, Expr::value(timestamp))
+            .col_expr(application_log_retention::Column::UpdatedAt, Expr::value(timestamp))
+            .filter(application_log_retention::Column::Hash.eq(hash))
+            .filter(application_log_retention::Column::DeletedAt.eq(0))
+            .exec(&self.state.database_core)
+            .await?;
+
+        Ok(())
+    }
+
+    /// the retention rules of the application, highest priority first
+    pub async fn log_retention(&self) -> anyhow::Result<Vec<RetentionRule>> {
+        let models = application_log_retention::Entity::find()
+            .filter(application_log_retention::Column::Application.eq(self.record.id))
+            .filter(application_log_retention::Column::DeletedAt.eq(0))
+            .all(&self.state.database_core)
+            .await?;
+
+        let mut rules = models.into_iter().map(RetentionRule::from).collect::<Vec<_>>();
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority()));
+        Ok(rules)
+    }
+
+    /// delete the logs that are past their retention, in batches. Logs without a matching rule are kept
+    pub async fn purge_logs(&self, config: &PurgeConfig) -> anyhow::Result<PurgeReport> {
+        let rules = self.log_retention().await?;
+        let batch_size = config.batch_size.max(1);
+        let now = unix_timestamp();
+        let mut report = PurgeReport::default();
+
+        for rule in rules.iter() {
+            let archive_dir = match (rule.archive, config.archive_dir.as_ref()) {
+                (true, Some(dir)) => Some(dir.join(&self.record.hash)),
+                (true, None) => {
+                    tracing::warn!(
+                        "Skipping log retention of application {} that archives without an archive directory",
+                        self.record.id
+                    );
+                    continue;
+                }
+                (false, _) => None,
+            };
+
+            let condition = Condition::all()
+                .add(application_process_logs::Column::Application.eq(self.record.id))
+                .add(application_process_logs::Column::CreatedAt.lt(now - rule.keep.as_secs() as i64))
+                .add(rule.condition(&rules));
+
+            loop {
+                let logs = application_process_logs::Entity::find()
+                    .filter(condition.clone())
+                    .order_by_asc(application_process_logs::Column::Id)
+                    .limit(batch_size)
+                    .all(&self.state.database_core)
+                    .await?;
+
+                if logs.is_empty() {
+                    break;
+                }
+
+                let amount = logs.len() as u64;
+                let ids = logs.iter().map(|log| log.id).collect::<Vec<_>>();
+
+                // entries are only deleted once the archive is safely on disk
+                if let Some(dir) = archive_dir.clone() {
+                    let file = tokio::task::spawn_blocking(move || archive_logs(&dir, logs)).await??;
+                    report.archived += amount;
+                    report.files.push(file);
+                }
+
+                let result = application_process_logs::Entity::delete_many()
+                    .filter(application_process_logs::Column::Id.is_in(ids))
+                    .exec(&self.state.database_core)
+                    .await?;
+                report.deleted += result.rows_affected;
+
+                if amount < batch_size {
+                    break;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// purge logs on an interval in the background. The first purge runs right away
+    pub fn auto_purge_logs(&self, interval: Duration, config: PurgeConfig) -> JoinHandle<()>
+    where
+        Extension: Send + Sync + 'static,
+    {
+        let app = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match app.purge_logs(&config).await {
+                    Ok(report) if report.deleted > 0 => {
+                        tracing::info!(
+                            "Purged {} logs of application {}, archived {}",
+                            report.deleted,
+                            app.record.id,
+                            report.archived
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::error!("Unable to purge logs of application {}: {}", app.record.id, err),
+                }
+            }
+        })
+    }
+}
+
+/// write the entries to a gzip compressed json lines file named after the first and last id
+fn archive_logs(dir: &Path, logs: Vec<application_process_logs::Model>) -> anyhow::Result<PathBuf> {
+    let first = logs.first().map_or(0, |log| log.id);
+    let last = logs.last().map_or(0, |log| log.id);
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}-{}.jsonl.gz", first, last));
+
+    let mut encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+    for log in logs.into_iter() {
+        serde_json::to_writer(&mut encoder, &ProcessLog::from(log))?;
+        encoder.write_all(b"\n")?;
+    }
+    encoder.finish()?.sync_all()?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn rule(process: Option<RecordId>, level: Option<LogLevel>) -> RetentionRule {
+        RetentionRule {
+            process,
+            level: level.map(|level| level as i8),
+            keep: Duration::from_secs(60),
+            archive: false,
+        }
+    }
+
+    #[test]
+    pub fn retention_rule_test() {
+        let all = rule(None, None);
+        let debug = rule(None, Some(LogLevel::Debug));
+        let errors = rule(None, Some(LogLevel::Error));
+        let process = rule(Some(4), None);
+        let process_debug = rule(Some(4), Some(LogLevel::Debug));
+
+        assert!(!debug.overlaps(&errors));
+        assert!(debug.overlaps(&process));
+        assert!(process_debug.overlaps(&all));
+        assert!(!process_debug.overlaps(&rule(Some(5), None)));
+        assert!(process.priority() > debug.priority());
+
+        let rules = vec![
+            all.clone(),
+            debug.clone(),
+            errors.clone(),
+            process.clone(),
+            process_debug.clone(),
+        ];
+        assert_eq!(
+            format!("{:?}", debug.condition(&rules)),
+            format!(
+                "{:?}",
+                debug
+                    .scope()
+                    .add(process.scope().not())
+                    .add(process_debug.scope().not())
+            )
+        );
+        assert_eq!(
+            format!("{:?}", process_debug.condition(&rules)),
+            format!("{:?}", process_debug.scope())
+        );
+    }
+
+    #[test]
+    pub fn archive_logs_test() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("levelcrush-archive-{}", uuid::Uuid::new_v4()));
+        let logs = (1..=3)
+            .map(|id| application_process_logs::Model {
+                id,
+                content: format!("entry {}", id),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let path = archive_logs(&dir, logs)?;
+        assert!(path.ends_with("1-3.jsonl.gz"));
+
+        let lines = BufReader::new(GzDecoder::new(File::open(&path)?))
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+        let first: ProcessLog = serde_json::from_str(&lines[0])?;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(first.content, "entry 1");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveValue, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::ApplicationProcess;
+use crate::alias::{RecordId, UnixTimestamp};
+use crate::app::Application;
+use crate::entities::application_process_runs;
+use crate::util::unix_timestamp;
+
+/// heartbeat interval to use when there is no reason to pick something else
+pub const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    /// still running as far as the database knows, but the heartbeat stopped coming in
+    Stale,
+}
+
+impl RunStatus {
+    /// the status that is stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Succeeded => "succeeded",
+            RunStatus::Failed => "failed",
+            RunStatus::Stale => "stale",
+        }
+    }
+
+    pub fn from_status(status: &str) -> Option<RunStatus> {
+        match status {
+            "running" => Some(RunStatus::Running),
+            "succeeded" => Some(RunStatus::Succeeded),
+            "failed" => Some(RunStatus::Failed),
+            "stale" => Some(RunStatus::Stale),
+            _ => None,
+        }
+    }
+}
+
+/// how a process is doing based on its latest run
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessHealth {
+    /// running with a recent heartbeat
+    Healthy,
+    /// running without a recent heartbeat
+    Stale,
+    /// the latest run finished with this status
+    Stopped(RunStatus),
+    /// the process has never been run
+    Unknown,
+}
+
+/// a single run of a process, from start to finish
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProcessRun {
+    pub id: RecordId,
+    pub process: RecordId,
+    /// unique to the run, so multiple instances of the same process can be told apart
+    pub instance: String,
+    pub host: String,
+    pub pid: i64,
+    pub status: RunStatus,
+    pub started_at: UnixTimestamp,
+    pub heartbeat_at: UnixTimestamp,
+    /// 0 while the run has not finished
+    pub finished_at: UnixTimestamp,
+}
+
+impl From<application_process_runs::Model> for ProcessRun {
+    fn from(model: application_process_runs::Model) -> Self {
+        ProcessRun {
+            id: model.id,
+            process: model.process,
+            instance: model.instance,
+            host: model.host,
+            pid: model.pid,
+            // anything we do not know about has not finished cleanly
+            status: RunStatus::from_status(&model.status).unwrap_or(RunStatus::Failed),
+            started_at: model.started_at,
+            heartbeat_at: model.heartbeat_at,
+            finished_at: model.finished_at,
+        }
+    }
+}
+
+/// health of a process from its latest run. Runs without a heartbeat within `max_age` of `now` are stale
+fn health(run: Option<&ProcessRun>, now: UnixTimestamp, max_age: Duration) -> ProcessHealth {
+    let run = match run {
+        Some(run) => run,
+        None => return ProcessHealth::Unknown,
+    };
+
+    match run.status {
+        RunStatus::Running | RunStatus::Stale if run.heartbeat_at >= now - max_age.as_secs() as i64 => {
+            ProcessHealth::Healthy
+        }
+        RunStatus::Running | RunStatus::Stale => ProcessHealth::Stale,
+        status => ProcessHealth::Stopped(status),
+    }
+}
+
+/// name of the machine we are running on, falling back to the host of the application
+fn hostname(fallback: &str) -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+impl<Extension> ApplicationProcess<Extension>
+where
+    Extension: Clone,
+{
+    /// the run started through this process, or a clone of it, that has not finished yet
+    pub fn run_id(&self) -> Option<RecordId> {
+        *self.run.lock().expect("Process run lock poisoned")
+    }
+
+    /// record that the process started running on this machine. Only one run can be active per process handle
+    pub async fn start_run(&self) -> anyhow::Result<ProcessRun> {
+        if self.run_id().is_some() {
+            return Err(anyhow!("Process {} already has an active run", self.record.name));
+        }
+
+        let timestamp = unix_timestamp();
+        let mut model = application_process_runs::Model {
+            id: 0,
+            application: self.application.record.id,
+            process: self.record.id,
+            instance: Uuid::new_v4().to_string(),
+            host: hostname(&self.application.record.host),
+            pid: std::process::id() as i64,
+            status: RunStatus::Running.as_str().to_string(),
+            started_at: timestamp,
+            heartbeat_at: timestamp,
+            finished_at: 0,
+            created_at: timestamp,
+            updated_at: 0,
+            deleted_at: 0,
+        };
+
+        let active = application_process_runs::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(model.application),
+            process: ActiveValue::Set(model.process),
+            instance: ActiveValue::Set(model.instance.clone()),
+            host: ActiveValue::Set(model.host.clone()),
+            pid: ActiveValue::Set(model.pid),
+            status: ActiveValue::Set(model.status.clone()),
+            started_at: ActiveValue::Set(model.started_at),
+            heartbeat_at: ActiveValue::Set(model.heartbeat_at),
+            finished_at: ActiveValue::Set(0),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(0),
+            deleted_at: ActiveValue::Set(0),
+        };
+
+        let result = application_process_runs::Entity::insert(active)
+            .exec(&self.application.state.database_core)
+            .await?;
+        model.id = result.last_insert_id;
+
+        *self.run.lock().expect("Process run lock poisoned") = Some(model.id);
+        Ok(ProcessRun::from(model))
+    }
+
+    /// let everyone know the active run is still alive. A run that was flagged stale is running again
+    pub async fn heartbeat(&self) -> anyhow::Result<()> {
+        let id = self
+            .run_id()
+            .ok_or_else(|| anyhow!("Process {} has no active run", self.record.name))?;
+
+        let timestamp = unix_timestamp();
+        let result = application_process_runs::Entity::update_many()
+            .col_expr(application_process_runs::Column::HeartbeatAt, Expr::value(timestamp))
+            .col_expr(
+                application_process_runs::Column::Status,
+                Expr::value(RunStatus::Running.as_str()),
+            )
+            .col_expr(application_process_runs::Column::UpdatedAt, Expr::value(timestamp))
+            .filter(application_process_runs::Column::Id.eq(id))
+            .filter(application_process_runs::Column::FinishedAt.eq(0))
+            .exec(&self.application.state.database_core)
+            .await?;
+
+        if result.rows_affected == 0 {
+            return Err(anyhow!(
+                "Run {} of process {} has already finished",
+                id,
+                self.record.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// record how the active run ended
+    pub async fn finish_run(&self, status: RunStatus) -> anyhow::Result<()> {
+        if status == RunStatus::Running {
+            return Err(anyhow!("A run can not finish as running"));
+        }
+
+        let id = self
+            .run_id()
+            .ok_or_else(|| anyhow!("Process {} has no active run", self.record.name))?;
+
+        let timestamp = unix_timestamp();
+        application_process_runs::Entity::update_many()
+            .col_expr(application_process_runs::Column::Status, Expr::value(status.as_str()))
+            .col_expr(application_process_runs::Column::HeartbeatAt, Expr::value(timestamp))
+            .col_expr(application_process_runs::Column::FinishedAt, Expr::value(timestamp))
+            .col_expr(application_process_runs::Column::UpdatedAt, Expr::value(timestamp))
+            .filter(application_process_runs::Column::Id.eq(id))
+            .exec(&self.application.state.database_core)
+            .await?;
+
+        // only let go of the run once it is recorded, so a failed update can be retried
+        let mut run = self.run.lock().expect("Process run lock poisoned");
+        if *run == Some(id) {
+            *run = None;
+        }
+
+        Ok(())
+    }
+
+    /// send a heartbeat on an interval in the background until the run finishes
+    pub fn start_heartbeat(&self, interval: Duration) -> JoinHandle<()>
+    where
+        Extension: Send + Sync + 'static,
+    {
+        let process = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the run has only just started
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if process.run_id().is_none() {
+                    break;
+                }
+                if let Err(err) = process.heartbeat().await {
+                    tracing::error!("Unable to send heartbeat of process {}: {}", process.record.name, err);
+                }
+            }
+        })
+    }
+
+    /// the most recent runs of the process, newest first
+    pub async fn runs(&self, limit: u64) -> anyhow::Result<Vec<ProcessRun>> {
+        let models = application_process_runs::Entity::find()
+            .filter(application_process_runs::Column::Process.eq(self.record.id))
+            .filter(application_process_runs::Column::DeletedAt.eq(0))
+            .order_by_desc(application_process_runs::Column::StartedAt)
+            .order_by_desc(application_process_runs::Column::Id)
+            .limit(limit)
+            .all(&self.application.state.database_core)
+            .await?;
+
+        Ok(models.into_iter().map(ProcessRun::from).collect())
+    }
+
+    /// health of the process based on its latest run. Runs without a heartbeat within `max_age` are stale
+    pub async fn health(&self, max_age: Duration) -> anyhow::Result<ProcessHealth> {
+        let latest = self.runs(1).await?;
+        Ok(health(latest.first(), unix_timestamp(), max_age))
+    }
+}
+
+impl<Extension> Application<Extension>
+where
+    Extension: Clone,
+{
+    /// flag running processes without a heartbeat within `max_age` as stale and return the runs that were flagged
+    pub async fn flag_stale_runs(&self, max_age: Duration) -> anyhow::Result<Vec<ProcessRun>> {
+        let cutoff = unix_timestamp() - max_age.as_secs() as i64;
+        let models = application_process_runs::Entity::find()
+            .filter(application_process_runs::Column::Application.eq(self.record.id))
+            .filter(application_process_runs::Column::Status.eq(RunStatus::Running.as_str()))
+            .filter(application_process_runs::Column::HeartbeatAt.lt(cutoff))
+            .filter(application_process_runs::Column::DeletedAt.eq(0))
+            .all(&self.state.database_core)
+            .await?;
+
+        // each run is flagged on its own so only the runs this call actually flagged are returned.
+        // a heartbeat or finish that lands in between wins, the run is not stale after all
+        let mut flagged = Vec::new();
+        for model in models.into_iter() {
+            let result = application_process_runs::Entity::update_many()
+                .col_expr(
+                    application_process_runs::Column::Status,
+                    Expr::value(RunStatus::Stale.as_str()),
+                )
+                .col_expr(
+                    application_process_runs::Column::UpdatedAt,
+                    Expr::value(unix_timestamp()),
+                )
+                .filter(application_process_runs::Column::Id.eq(model.id))
+                .filter(application_process_runs::Column::Status.eq(RunStatus::Running.as_str()))
+                .filter(application_process_runs::Column::HeartbeatAt.lt(cutoff))
+                .exec(&self.state.database_core)
+                .await?;
+
+            if result.rows_affected > 0 {
+                flagged.push(ProcessRun {
+                    status: RunStatus::Stale,
+                    ..ProcessRun::from(model)
+                });
+            }
+        }
+
+        Ok(flagged)
+    }
+
+    /// runs of every process that are currently flagged stale
+    pub async fn stale_runs(&self) -> anyhow::Result<Vec<ProcessRun>> {
+        let models = application_process_runs::Entity::find()
+            .filter(application_process_runs::Column::Application.eq(self.record.id))
+            .filter(application_process_runs::Column::Status.eq(RunStatus::Stale.as_str()))
+            .filter(application_process_runs::Column::DeletedAt.eq(0))
+            .order_by_asc(application_process_runs::Column::HeartbeatAt)
+            .all(&self.state.database_core)
+            .await?;
+
+        Ok(models.into_iter().map(ProcessRun::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(status: RunStatus, heartbeat_at: UnixTimestamp) -> ProcessRun {
+        ProcessRun {
+            id: 1,
+            process: 1,
+            instance: String::new(),
+            host: String::new(),
+            pid: 0,
+            status,
+            started_at: 0,
+            heartbeat_at,
+            finished_at: 0,
+        }
+    }
+
+    #[test]
+    pub fn process_health_test() {
+        let max_age = Duration::from_secs(60);
+        assert_eq!(health(None, 1000, max_age), ProcessHealth::Unknown);
+        assert_eq!(
+            health(Some(&run(RunStatus::Running, 950)), 1000, max_age),
+            ProcessHealth::Healthy
+        );
+        assert_eq!(
+            health(Some(&run(RunStatus::Running, 900)), 1000, max_age),
+            ProcessHealth::Stale
+        );
+        // flagged stale, but the heartbeat came back
+        assert_eq!(
+            health(Some(&run(RunStatus::Stale, 990)), 1000, max_age),
+            ProcessHealth::Healthy
+        );
+        assert_eq!(
+            health(Some(&run(RunStatus::Failed, 100)), 1000, max_age),
+            ProcessHealth::Stopped(RunStatus::Failed)
+        );
+
+        assert_eq!(RunStatus::from_status("succeeded"), Some(RunStatus::Succeeded));
+        assert_eq!(
+            RunStatus::from_status(RunStatus::Stale.as_str()),
+            Some(RunStatus::Stale)
+        );
+        assert_eq!(RunStatus::from_status("crashed"), None);
+    }
+}
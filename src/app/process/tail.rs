@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use sea_orm::ActiveValue;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::query::ProcessLog;
+use crate::alias::RecordId;
+use crate::entities::application_process_logs;
+
+/// how many entries a live subscriber can fall behind before it starts missing them
+pub const TAIL_CAPACITY: usize = 1024;
+
+/// which live entries a subscriber receives
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogTailFilter {
+    pub process: Option<RecordId>,
+    /// only entries that are at least as severe as the level. Warning includes errors, but not info
+    pub level: Option<i8>,
+    pub sub_id: Option<String>,
+}
+
+impl LogTailFilter {
+    pub fn matches(&self, log: &ProcessLog) -> bool {
+        self.process.is_none_or(|process| process == log.process)
+            && self.level.is_none_or(|level| log.level <= level)
+            && self.sub_id.as_ref().is_none_or(|sub_id| *sub_id == log.sub_id)
+    }
+}
+
+struct TailState {
+    recent: VecDeque<ProcessLog>,
+    replay: usize,
+}
+
+/// in process broadcast of log entries as they are handed to the log writer.
+/// keeps the most recent entries around so new subscribers can catch up
+pub struct LogTail {
+    sender: broadcast::Sender<ProcessLog>,
+    state: Mutex<TailState>,
+}
+
+impl LogTail {
+    /// keep up to `replay` recent entries for new subscribers
+    pub fn new(replay: usize) -> LogTail {
+        let (sender, _) = broadcast::channel(TAIL_CAPACITY);
+        LogTail {
+            sender,
+            state: Mutex::new(TailState {
+                recent: VecDeque::with_capacity(replay),
+                replay,
+            }),
+        }
+    }
+
+    /// send an entry to every subscriber. Skipped entirely when nobody is listening and nothing is replayed
+    pub(crate) fn publish(&self, model: &application_process_logs::ActiveModel) {
+        let mut state = self.state.lock().expect("Log tail lock poisoned");
+        if state.replay == 0 && self.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let log = tail_entry(model);
+        if state.replay > 0 {
+            if state.recent.len() >= state.replay {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(log.clone());
+        }
+
+        // no receivers is not an error
+        let _ = self.sender.send(log);
+    }
+
+    /// receive entries matching the filter, starting with up to `replay` of the most recent ones
+    pub fn subscribe(&self, filter: LogTailFilter, replay: usize) -> LogSubscription {
+        // holding the lock keeps a publish from landing between the replay and the live entries
+        let state = self.state.lock().expect("Log tail lock poisoned");
+        let mut recent = state
+            .recent
+            .iter()
+            .rev()
+            .filter(|log| filter.matches(log))
+            .take(replay)
+            .cloned()
+            .collect::<VecDeque<_>>();
+        recent.make_contiguous().reverse();
+
+        LogSubscription {
+            filter,
+            replay: recent,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+/// live log entries of an application. Created through `LogTail::subscribe`
+pub struct LogSubscription {
+    filter: LogTailFilter,
+    replay: VecDeque<ProcessLog>,
+    receiver: broadcast::Receiver<ProcessLog>,
+}
+
+impl LogSubscription {
+    /// wait for the next matching entry. None once the log writer has been dropped
+    pub async fn recv(&mut self) -> Option<ProcessLog> {
+        if let Some(log) = self.replay.pop_front() {
+            return Some(log);
+        }
+
+        loop {
+            match self.receiver.recv().await {
+                Ok(log) if self.filter.matches(&log) => return Some(log),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Log subscription fell behind and missed {} entries", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+fn value<V>(value: &ActiveValue<V>) -> V
+where
+    V: Clone + Default + Into<sea_orm::Value>,
+{
+    match value {
+        ActiveValue::Set(value) | ActiveValue::Unchanged(value) => value.clone(),
+        ActiveValue::NotSet => V::default(),
+    }
+}
+
+/// the entry as subscribers see it. The id is 0, entries are inserted in batches that do not return their ids
+fn tail_entry(model: &application_process_logs::ActiveModel) -> ProcessLog {
+    ProcessLog::from(application_process_logs::Model {
+        id: value(&model.id),
+        application: value(&model.application),
+        process: value(&model.process),
+        hash: String::new(),
+        hash_sub: String::new(),
+        r#type: value(&model.r#type),
+        content: value(&model.content),
+        fields: value(&model.fields),
+        sub_id: value(&model.sub_id),
+        correlation_id: value(&model.correlation_id),
+        module: value(&model.module),
+        line: value(&model.line),
+        created_at: value(&model.created_at),
+        updated_at: 0,
+        deleted_at: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::process::record::LogRecord;
+    use crate::app::process::LogLevel;
+
+    fn model(process: RecordId, level: LogLevel, content: &str) -> application_process_logs::ActiveModel {
+        LogRecord::new(level, content)
+            .with_sub_id("mock")
+            .into_model(1, process)
+    }
+
+    #[tokio::test]
+    pub async fn log_tail_test() {
+        let tail = LogTail::new(3);
+        tail.publish(&model(1, LogLevel::Info, "first"));
+        tail.publish(&model(2, LogLevel::Error, "second"));
+        tail.publish(&model(1, LogLevel::Debug, "third"));
+        tail.publish(&model(1, LogLevel::Warning, "fourth"));
+
+        let filter = LogTailFilter {
+            process: Some(1),
+            level: Some(LogLevel::Info as i8),
+            ..Default::default()
+        };
+        let mut subscription = tail.subscribe(filter, 10);
+        tail.publish(&model(2, LogLevel::Error, "other process"));
+        tail.publish(&model(1, LogLevel::Error, "live"));
+
+        // "first" no longer fits in the replay, "third" is too verbose
+        assert_eq!(
+            subscription.recv().await.map(|log| log.content),
+            Some("fourth".to_string())
+        );
+        assert_eq!(
+            subscription.recv().await.map(|log| log.content),
+            Some("live".to_string())
+        );
+
+        let mut latest = tail.subscribe(LogTailFilter::default(), 1);
+        drop(tail);
+        assert_eq!(latest.recv().await.map(|log| log.sub_id), Some("mock".to_string()));
+        assert_eq!(latest.recv().await, None);
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tokio::sync::{mpsc, oneshot, Notify};
+
+use super::tail::{LogSubscription, LogTail, LogTailFilter};
+use crate::alias::RecordId;
+use crate::entities::application_process_logs;
+
+/// what happens to a new log entry when the buffer is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// make room by dropping the oldest entry that has not been written yet
+    DropOldest,
+    /// drop the new entry
+    DropNewest,
+    /// wait until the writer has made room. Only `write_wait` can wait, `write` drops the new entry instead
+    Block,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogWriterConfig {
+    /// entries that can be buffered before the backpressure policy kicks in
+    pub capacity: usize,
+    /// most entries written in a single insert. Reaching it triggers a write right away
+    pub batch_size: usize,
+    /// how often buffered entries are written when the batch size is not reached
+    pub interval: Duration,
+    pub policy: BackpressurePolicy,
+    /// recent entries kept around for live subscribers that ask for a replay
+    pub replay: usize,
+}
+
+impl Default for LogWriterConfig {
+    fn default() -> Self {
+        LogWriterConfig {
+            capacity: 4096,
+            batch_size: 256,
+            interval: Duration::from_secs(1),
+            policy: BackpressurePolicy::DropOldest,
+            replay: 100,
+        }
+    }
+}
+
+/// the outcome of a single entry, sent back once it has been written or dropped
+type LogAck = oneshot::Sender<Result<(), String>>;
+
+struct LogEntry {
+    model: application_process_logs::ActiveModel,
+    ack: LogAck,
+}
+
+/// bounded buffer of entries that have not been written yet
+struct LogQueue {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+}
+
+impl LogQueue {
+    /// add an entry following the backpressure policy. The entry is handed back when we have to wait for room
+    fn push(&mut self, entry: LogEntry, dropped: &AtomicU64) -> Result<(), Box<LogEntry>> {
+        if self.entries.len() < self.capacity {
+            self.entries.push_back(entry);
+            return Ok(());
+        }
+
+        match self.policy {
+            BackpressurePolicy::DropOldest => {
+                if let Some(oldest) = self.entries.pop_front() {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    let _ = oldest
+                        .ack
+                        .send(Err("Log entry was dropped, the buffer was full".to_string()));
+                }
+                self.entries.push_back(entry);
+                Ok(())
+            }
+            BackpressurePolicy::DropNewest => {
+                dropped.fetch_add(1, Ordering::Relaxed);
+                let _ = entry
+                    .ack
+                    .send(Err("Log entry was dropped, the buffer was full".to_string()));
+                Ok(())
+            }
+            BackpressurePolicy::Block => Err(Box::new(entry)),
+        }
+    }
+
+    fn take(&mut self, limit: usize) -> Vec<LogEntry> {
+        let amount = self.entries.len().min(limit);
+        self.entries.drain(..amount).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+struct LogShared {
+    queue: Mutex<LogQueue>,
+    batch_size: usize,
+    dropped: AtomicU64,
+    /// wakes the writer once a full batch is waiting
+    ready: Notify,
+    /// wakes anything blocked on a full buffer
+    space: Notify,
+}
+
+impl LogShared {
+    fn push(&self, entry: LogEntry) -> Result<(), Box<LogEntry>> {
+        let mut queue = self.queue.lock().expect("Log queue lock poisoned");
+        queue.push(entry, &self.dropped)?;
+        if queue.len() >= self.batch_size {
+            self.ready.notify_one();
+        }
+        Ok(())
+    }
+
+    async fn push_wait(&self, mut entry: LogEntry) {
+        loop {
+            let space = self.space.notified();
+            match self.push(entry) {
+                Ok(_) => return,
+                Err(rejected) => {
+                    entry = *rejected;
+                    self.ready.notify_one();
+                    space.await;
+                }
+            }
+        }
+    }
+
+    fn take(&self) -> Vec<LogEntry> {
+        let batch = self
+            .queue
+            .lock()
+            .expect("Log queue lock poisoned")
+            .take(self.batch_size);
+        self.space.notify_waiters();
+        batch
+    }
+}
+
+/// buffers process log entries and writes them in multi row inserts from a single background task.
+/// clones share the same buffer. The background task stops once every clone has been dropped, writing whatever is left
+#[derive(Clone)]
+pub struct LogWriter {
+    shared: Arc<LogShared>,
+    flushes: mpsc::UnboundedSender<oneshot::Sender<()>>,
+    tail: Arc<LogTail>,
+}
+
+impl LogWriter {
+    /// start a writer on the database. Must be called from within a tokio runtime
+    pub fn new(database: DatabaseConnection, config: LogWriterConfig) -> LogWriter {
+        let batch_size = config.batch_size.max(1);
+        let shared = Arc::new(LogShared {
+            queue: Mutex::new(LogQueue {
+                entries: VecDeque::new(),
+                capacity: config.capacity.max(1),
+                policy: config.policy,
+            }),
+            batch_size,
+            dropped: AtomicU64::new(0),
+            ready: Notify::new(),
+            space: Notify::new(),
+        });
+
+        let (flushes, mut flush_requests) = mpsc::unbounded_channel::<oneshot::Sender<()>>();
+        let tail = Arc::new(LogTail::new(config.replay));
+        let task_shared = shared.clone();
+        let task_tail = tail.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(config.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                let (flush, closed) = tokio::select! {
+                    _ = ticker.tick() => (None, false),
+                    _ = task_shared.ready.notified() => (None, false),
+                    request = flush_requests.recv() => match request {
+                        Some(request) => (Some(request), false),
+                        None => (None, true),
+                    },
+                };
+
+                loop {
+                    let batch = task_shared.take();
+                    if batch.is_empty() {
+                        break;
+                    }
+                    write_batch(&database, batch, &task_shared.dropped, &task_tail).await;
+                }
+
+                if let Some(flush) = flush {
+                    let _ = flush.send(());
+                }
+                if closed {
+                    break;
+                }
+            }
+        });
+
+        LogWriter { shared, flushes, tail }
+    }
+
+    /// buffer a log entry without waiting. When the buffer is full and the policy is to block, the new entry is dropped.
+    /// use `write_wait` from async code that has to keep every entry
+    pub fn write(&self, model: application_process_logs::ActiveModel) -> LogHandle {
+        let (ack, receiver) = oneshot::channel();
+        if let Err(entry) = self.shared.push(LogEntry { model, ack }) {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            let _ = entry
+                .ack
+                .send(Err("Log entry was dropped, the buffer was full".to_string()));
+        }
+        LogHandle { receiver }
+    }
+
+    /// buffer a log entry, waiting for room when the buffer is full and the policy is to block
+    pub async fn write_wait(&self, model: application_process_logs::ActiveModel) -> LogHandle {
+        let (ack, receiver) = oneshot::channel();
+        self.shared.push_wait(LogEntry { model, ack }).await;
+        LogHandle { receiver }
+    }
+
+    /// write everything that is buffered right now and wait for it to complete. Intended for shutdown
+    pub async fn flush(&self) {
+        let (request, done) = oneshot::channel();
+        if self.flushes.send(request).is_ok() {
+            let _ = done.await;
+        }
+    }
+
+    /// entries that never made it to the database, either dropped by the backpressure policy or lost to a failed insert
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// entries waiting to be written
+    pub fn pending(&self) -> usize {
+        self.shared.queue.lock().expect("Log queue lock poisoned").len()
+    }
+
+    /// entries as they are written, starting with up to `replay` of the most recent ones that match the filter.
+    /// entries that are dropped or fail to insert never reach subscribers, so they see exactly what a `LogQuery` would
+    pub fn subscribe(&self, filter: LogTailFilter, replay: usize) -> LogSubscription {
+        self.tail.subscribe(filter, replay)
+    }
+}
+
+/// the log writer of every application, shared by all `Application` instances made from the same `ApplicationState`.
+/// each application gets a single buffer and background task no matter how many times it is looked up
+#[derive(Clone, Default)]
+pub struct LogWriters {
+    writers: Arc<Mutex<HashMap<RecordId, LogWriter>>>,
+}
+
+impl LogWriters {
+    /// the writer of the application, started with the default configuration when it has none yet
+    pub fn get_or_start(&self, application: RecordId, database: &DatabaseConnection) -> LogWriter {
+        self.writers
+            .lock()
+            .expect("Log writers lock poisoned")
+            .entry(application)
+            .or_insert_with(|| LogWriter::new(database.clone(), LogWriterConfig::default()))
+            .clone()
+    }
+
+    /// start a new writer for the application, replacing the current one.
+    /// the replaced writer writes what it has buffered once its last clone is dropped
+    pub fn start(&self, application: RecordId, database: &DatabaseConnection, config: LogWriterConfig) -> LogWriter {
+        let writer = LogWriter::new(database.clone(), config);
+        self.writers
+            .lock()
+            .expect("Log writers lock poisoned")
+            .insert(application, writer.clone());
+        writer
+    }
+
+    /// the writer of the application, if one was started
+    pub fn get(&self, application: RecordId) -> Option<LogWriter> {
+        self.writers
+            .lock()
+            .expect("Log writers lock poisoned")
+            .get(&application)
+            .cloned()
+    }
+}
+
+/// insert the batch in a single statement and let every entry know how it went
+async fn write_batch(database: &DatabaseConnection, batch: Vec<LogEntry>, dropped: &AtomicU64, tail: &LogTail) {
+    let (models, acks): (Vec<_>, Vec<_>) = batch.into_iter().map(|entry| (entry.model, entry.ack)).unzip();
+    let amount = models.len();
+
+    let result = application_process_logs::Entity::insert_many(models.clone())
+        .exec_without_returning(database)
+        .await;
+
+    let outcome = match result {
+        Ok(_) => {
+            // only entries that were stored reach live subscribers
+            for model in models.iter() {
+                tail.publish(model);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            dropped.fetch_add(amount as u64, Ordering::Relaxed);
+            tracing::error!("Unable to write {} process log entries: {}", amount, err);
+            Err(err.to_string())
+        }
+    };
+
+    for ack in acks.into_iter() {
+        let _ = ack.send(outcome.clone());
+    }
+}
+
+/// resolves once the log entry has been written. Awaiting it is optional
+pub struct LogHandle {
+    receiver: oneshot::Receiver<Result<(), String>>,
+}
+
+impl LogHandle {
+    /// a handle for an entry that was filtered out before reaching the writer. Resolves right away
+    pub(crate) fn skipped() -> LogHandle {
+        let (ack, receiver) = oneshot::channel();
+        let _ = ack.send(Ok(()));
+        LogHandle { receiver }
+    }
+}
+
+impl Future for LogHandle {
+    type Output = anyhow::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(Ok(()))) => Poll::Ready(Ok(())),
+            Poll::Ready(Ok(Err(err))) => Poll::Ready(Err(anyhow!(err))),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(anyhow!("Log writer stopped before the entry was written"))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::ActiveValue;
+
+    use super::*;
+
+    fn mock_entry(content: &str) -> (LogEntry, oneshot::Receiver<Result<(), String>>) {
+        let (ack, receiver) = oneshot::channel();
+        let model = application_process_logs::ActiveModel {
+            content: ActiveValue::Set(content.to_string()),
+            ..Default::default()
+        };
+        (LogEntry { model, ack }, receiver)
+    }
+
+    fn queue(policy: BackpressurePolicy) -> LogQueue {
+        LogQueue {
+            entries: VecDeque::new(),
+            capacity: 2,
+            policy,
+        }
+    }
+
+    fn contents(queue: &mut LogQueue) -> Vec<String> {
+        queue
+            .take(10)
+            .into_iter()
+            .map(|entry| match entry.model.content {
+                ActiveValue::Set(content) => content,
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn log_queue_policy_test() {
+        let dropped = AtomicU64::new(0);
+
+        let mut oldest = queue(BackpressurePolicy::DropOldest);
+        let (first, mut first_ack) = mock_entry("1");
+        assert!(oldest.push(first, &dropped).is_ok());
+        assert!(oldest.push(mock_entry("2").0, &dropped).is_ok());
+        assert!(oldest.push(mock_entry("3").0, &dropped).is_ok());
+        assert_eq!(contents(&mut oldest), vec!["2", "3"]);
+        assert!(matches!(first_ack.try_recv(), Ok(Err(_))));
+
+        let mut newest = queue(BackpressurePolicy::DropNewest);
+        for content in ["1", "2", "3"] {
+            assert!(newest.push(mock_entry(content).0, &dropped).is_ok());
+        }
+        assert_eq!(contents(&mut newest), vec!["1", "2"]);
+
+        let mut block = queue(BackpressurePolicy::Block);
+        for content in ["1", "2"] {
+            assert!(block.push(mock_entry(content).0, &dropped).is_ok());
+        }
+        assert!(block.push(mock_entry("3").0, &dropped).is_err());
+        assert_eq!(block.len(), 2);
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    pub async fn log_writers_test() {
+        let writers = LogWriters::default();
+        let database = DatabaseConnection::Disconnected;
+
+        let first = writers.get_or_start(1, &database);
+        assert!(Arc::ptr_eq(&first.shared, &writers.get_or_start(1, &database).shared));
+        assert!(!Arc::ptr_eq(&first.shared, &writers.get_or_start(2, &database).shared));
+
+        let replaced = writers.start(1, &database, LogWriterConfig::default());
+        let current = writers.get(1).expect("Writer was not registered");
+        assert!(Arc::ptr_eq(&replaced.shared, &current.shared));
+        assert!(!Arc::ptr_eq(&first.shared, &current.shared));
+        assert!(writers.get(3).is_none());
+    }
+
+    #[tokio::test]
+    pub async fn log_writer_block_test() {
+        let writer = LogWriter::new(
+            DatabaseConnection::Disconnected,
+            LogWriterConfig {
+                capacity: 1,
+                interval: Duration::from_secs(60),
+                policy: BackpressurePolicy::Block,
+                ..Default::default()
+            },
+        );
+
+        // the sync path never waits, the overflowing entry is dropped and counted
+        let _first = writer.write(mock_entry("1").0.model);
+        assert!(writer.write(mock_entry("2").0.model).await.is_err());
+        assert_eq!(writer.dropped(), 1);
+        assert_eq!(writer.pending(), 1);
+
+        // nothing reaches live subscribers before it is stored, so the dropped entry never does
+        let mut subscription = writer.subscribe(LogTailFilter::default(), 10);
+        assert!(tokio::time::timeout(Duration::from_millis(50), subscription.recv())
+            .await
+            .is_err());
+    }
+}
@@ -1,16 +1,41 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use anyhow::anyhow;
-use sea_orm::{ActiveModelTrait, ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter};
+use futures::StreamExt;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::de::DeserializeOwned;
+use tokio::sync::{broadcast, RwLock};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+pub mod binding;
+pub mod events;
+pub mod export;
+pub mod schema;
+pub mod scope;
+pub mod secret;
+mod write;
+
 use super::Application;
 use crate::{
-    alias::UnixTimestamp,
-    entities::{application_global_settings, application_settings, application_user_settings},
+    alias::{RecordId, UnixTimestamp},
+    cache::CacheDuration,
+    entities::{
+        application_global_settings, application_scoped_settings, application_settings, application_settings_history,
+        application_user_settings,
+    },
+    env::EnvVar,
     util::unix_timestamp,
 };
+use events::{DatabasePolling, SettingEvent, SettingsTransport, EVENT_CAPACITY};
+use schema::{SettingSchema, SettingValueType};
+use secret::SecretKey;
+use write::{SettingWrite, WriteQueue};
 
 #[derive(Clone)]
 pub struct ApplicationSettings<Extension>
@@ -20,13 +45,105 @@ where
     application: Application<Extension>,
     names: HashMap<i64, String>,
     base: HashMap<String, (application_settings::Model, UnixTimestamp)>,
+    schemas: HashMap<String, SettingSchema>,
     global: HashMap<String, (application_global_settings::Model, UnixTimestamp)>,
     user: HashMap<(String, String), (application_user_settings::Model, UnixTimestamp)>,
+    users: HashMap<String, UnixTimestamp>,
+    scoped: HashMap<(ApplicationSettingType, String, String), (application_scoped_settings::Model, UnixTimestamp)>,
+    scopes: HashMap<(ApplicationSettingType, String), UnixTimestamp>,
+    user_limit: usize,
+    user_duration: CacheDuration,
+    actor: String,
+    origin: String,
+    events: broadcast::Sender<SettingEvent>,
+    transport: Arc<dyn SettingsTransport>,
+    writes: WriteQueue,
+    secret_key: Option<SecretKey>,
+    last_sync: UnixTimestamp,
+}
+
+/// default amount of users (and separately guilds and channels) whose settings are kept in memory at once
+pub const DEFAULT_USER_CACHE_LIMIT: usize = 1024;
+
+/// seconds before the newest change we have seen that are fetched again on every refresh.
+/// covers clock drift between instances and writes that commit after a newer one
+pub const SYNC_OVERLAP: i64 = 5;
+
+/// rows that changed since the last sync, fetched by `ApplicationSettings::changes`
+struct SettingsChanges {
+    core: Vec<application_settings::Model>,
+    global: Vec<application_global_settings::Model>,
+    user: Vec<application_user_settings::Model>,
+    scoped: Vec<application_scoped_settings::Model>,
+}
+
+/// the most recent created, updated or deleted timestamp of a row
+fn changed_at(created_at: UnixTimestamp, updated_at: UnixTimestamp, deleted_at: UnixTimestamp) -> UnixTimestamp {
+    created_at.max(updated_at).max(deleted_at)
+}
+
+/// where the next refresh should start from. Only moves forward and is based on timestamps written to
+/// the database, so the clock of this instance never decides what is skipped
+fn next_sync(last_sync: UnixTimestamp, newest: Option<UnixTimestamp>) -> UnixTimestamp {
+    match newest {
+        Some(newest) => last_sync.max(newest - SYNC_OVERLAP),
+        None => last_sync,
+    }
 }
 
+/// keys of a loaded map that have expired, plus the oldest remaining keys while we are over the limit
+fn expired_entries<K>(loaded: &HashMap<K, UnixTimestamp>, limit: usize, duration: i64) -> Vec<K>
+where
+    K: Clone,
+{
+    let now = unix_timestamp();
+    let (mut expired, mut remaining): (Vec<_>, Vec<_>) = loaded
+        .iter()
+        .map(|(key, loaded_at)| (*loaded_at, key.clone()))
+        .partition(|(loaded_at, _)| now - loaded_at > duration);
+
+    if remaining.len() > limit {
+        remaining.sort_by_key(|(loaded_at, _)| *loaded_at);
+        let over = remaining.len() - limit;
+        expired.extend(remaining.into_iter().take(over));
+    }
+
+    expired.into_iter().map(|(_, key)| key).collect()
+}
+
+/// application settings that can be shared between tasks. Returned by `ApplicationSettings::auto_refresh`
+pub type SharedApplicationSettings<Extension> = Arc<RwLock<ApplicationSettings<Extension>>>;
+
+/// the scope a setting value is stored at.
+/// guild and channel values are stored with the guild or channel id where user values are stored with the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ApplicationSettingType {
     Global,
     User,
+    Guild,
+    Channel,
+}
+
+impl ApplicationSettingType {
+    /// the scope that is stored in the database and recorded in the settings history
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApplicationSettingType::Global => "global",
+            ApplicationSettingType::User => "user",
+            ApplicationSettingType::Guild => "guild",
+            ApplicationSettingType::Channel => "channel",
+        }
+    }
+
+    pub fn from_scope(scope: &str) -> Option<ApplicationSettingType> {
+        match scope {
+            "global" => Some(ApplicationSettingType::Global),
+            "user" => Some(ApplicationSettingType::User),
+            "guild" => Some(ApplicationSettingType::Guild),
+            "channel" => Some(ApplicationSettingType::Channel),
+            _ => None,
+        }
+    }
 }
 
 impl<Extension> ApplicationSettings<Extension>
@@ -34,10 +151,12 @@ where
     Extension: Clone,
 {
     /// load the core application settinggs and global settings from the database. This is intended ot be run on the first load of the application
+    /// secret settings are encrypted with the key in `SETTINGS_KEY` when it is set
     pub async fn load(app: &Application<Extension>) -> anyhow::Result<ApplicationSettings<Extension>> {
         let mut base = HashMap::new();
         let mut global = HashMap::new();
         let mut names = HashMap::new();
+        let mut schemas = HashMap::new();
         let user = HashMap::new();
         let timestamp = unix_timestamp();
 
@@ -54,6 +173,7 @@ where
         for model in core_settings.iter() {
             settings_ids.push(model.id);
             names.insert(model.id, model.name.clone());
+            schemas.insert(model.name.clone(), SettingSchema::from_model(model));
             base.insert(model.name.clone(), (model.clone(), timestamp));
         }
 
@@ -67,6 +187,16 @@ where
             .all(&app.state.database_core)
             .await?;
 
+        let newest = core_settings
+            .iter()
+            .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at))
+            .chain(
+                global_settings
+                    .iter()
+                    .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at)),
+            )
+            .max();
+
         let base_string = "".to_string();
         for setting in global_settings.iter() {
             //global.insert(setting.setting)
@@ -77,21 +207,385 @@ where
         Ok(ApplicationSettings {
             application: app.clone(),
             base,
+            schemas,
             global,
             names,
             user,
+            users: HashMap::new(),
+            scoped: HashMap::new(),
+            scopes: HashMap::new(),
+            user_limit: DEFAULT_USER_CACHE_LIMIT,
+            user_duration: CacheDuration::TenMinutes,
+            actor: String::new(),
+            origin: Uuid::new_v4().to_string(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            transport: Arc::new(DatabasePolling),
+            writes: WriteQueue::start(app.state.database_core.clone()),
+            secret_key: SecretKey::from_env(EnvVar::SettingsKey)?,
+            last_sync: next_sync(0, newest),
         })
     }
 
-    /// refresh the current settings in the data structure with the refreshed values
-    /// todo: **This is non functional right now.** This is more so for user settings
-    /// good for long running processes that have lots of user interaction and configuration
-    pub async fn refresh(&mut self) -> &mut Self {
-        // todo: this needs to be filled out
+    /// configure how many users have their settings cached at once and how long a user stays cached before being reloaded
+    pub fn with_user_cache(mut self, limit: usize, duration: CacheDuration) -> Self {
+        self.user_limit = limit.max(1);
+        self.user_duration = duration;
+        self.prune_users();
+        self
+    }
 
+    /// who is making changes through this instance. Recorded in the settings history
+    pub fn with_actor(mut self, actor: &str) -> Self {
+        self.actor = actor.to_string();
         self
     }
 
+    /// change who is making changes through this instance. Recorded in the settings history
+    pub fn set_actor(&mut self, actor: &str) -> &mut Self {
+        self.actor = actor.to_string();
+        self
+    }
+
+    /// how changes are shared with other running instances. Defaults to polling the database through `refresh`
+    pub fn with_transport<T: SettingsTransport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// load all settings of a single user into the cache in one query.
+    /// anything that was cached for the user before is replaced
+    pub async fn load_user(&mut self, user: &str) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        let models = application_user_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_user_settings::Column::Application.eq(self.application.record.id))
+                    .add(application_user_settings::Column::HashUser.eq(user))
+                    .add(application_user_settings::Column::DeletedAt.eq(0)),
+            )
+            .all(&self.application.state.database_core)
+            .await?;
+
+        self.user.retain(|(cached_user, _), _| cached_user != user);
+        for model in models.into_iter() {
+            if let Some(name) = self.names.get(&model.setting) {
+                self.user.insert((user.to_string(), name.clone()), (model, timestamp));
+            }
+        }
+
+        self.users.insert(user.to_string(), timestamp);
+        self.prune_users();
+        Ok(())
+    }
+
+    /// checks if all settings of the user are in the cache and have not expired
+    pub fn is_user_loaded(&self, user: &str) -> bool {
+        self.users
+            .get(user)
+            .is_some_and(|loaded_at| unix_timestamp() - loaded_at <= self.user_duration.i64())
+    }
+
+    /// drop the cached settings of the user
+    pub fn unload_user(&mut self, user: &str) {
+        self.users.remove(user);
+        self.user.retain(|(cached_user, _), _| cached_user != user);
+    }
+
+    /// removes expired users and scopes from the cache and then the oldest loaded until we are within the limit
+    fn prune_users(&mut self) {
+        let duration = self.user_duration.i64();
+
+        let expired = expired_entries(&self.users, self.user_limit, duration);
+        if !expired.is_empty() {
+            for user in expired.iter() {
+                self.users.remove(user);
+            }
+            self.user
+                .retain(|(cached_user, _), _| self.users.contains_key(cached_user));
+        }
+
+        let expired = expired_entries(&self.scopes, self.user_limit, duration);
+        if !expired.is_empty() {
+            for scope in expired.iter() {
+                self.scopes.remove(scope);
+            }
+            self.scoped
+                .retain(|(setting_type, id, _), _| self.scopes.contains_key(&(*setting_type, id.clone())));
+        }
+    }
+
+    /// refresh the cached settings with anything that has changed in the database since the last sync.
+    /// soft deleted settings are evicted from the cache. Subscribers are notified of every value that changed.
+    /// good for long running processes that have lots of user interaction and configuration
+    pub async fn refresh(&mut self) -> anyhow::Result<&mut Self> {
+        let changes = self.changes().await?;
+        self.merge(changes);
+        Ok(self)
+    }
+
+    /// fetch everything that changed since the last sync. Only needs read access so a shared cache
+    /// can keep serving reads while the queries run
+    async fn changes(&self) -> anyhow::Result<SettingsChanges> {
+        // every write bumps updated_at, so this one column covers creates and deletes through the (application, updated_at) index.
+        // rows within the overlap are fetched again on every refresh. Merging is idempotent so this is safe
+        let since = self.last_sync;
+        let application_id = self.application.record.id;
+        let database = &self.application.state.database_core;
+
+        let core_settings = application_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_settings::Column::Application.eq(application_id))
+                    .add(application_settings::Column::UpdatedAt.gte(since)),
+            )
+            .all(database)
+            .await?;
+
+        let global_settings = application_global_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_global_settings::Column::Application.eq(application_id))
+                    .add(application_global_settings::Column::UpdatedAt.gte(since)),
+            )
+            .all(database)
+            .await?;
+
+        let user_settings = application_user_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_user_settings::Column::Application.eq(application_id))
+                    .add(application_user_settings::Column::UpdatedAt.gte(since)),
+            )
+            .all(database)
+            .await?;
+
+        let scoped_settings = application_scoped_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_scoped_settings::Column::Application.eq(application_id))
+                    .add(application_scoped_settings::Column::UpdatedAt.gte(since)),
+            )
+            .all(database)
+            .await?;
+
+        Ok(SettingsChanges {
+            core: core_settings,
+            global: global_settings,
+            user: user_settings,
+            scoped: scoped_settings,
+        })
+    }
+
+    /// apply fetched rows to the cache and notify subscribers of every value that changed
+    fn merge(&mut self, fetched: SettingsChanges) {
+        let timestamp = unix_timestamp();
+        let newest = fetched
+            .core
+            .iter()
+            .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at))
+            .chain(
+                fetched
+                    .global
+                    .iter()
+                    .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at)),
+            )
+            .chain(
+                fetched
+                    .user
+                    .iter()
+                    .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at)),
+            )
+            .chain(
+                fetched
+                    .scoped
+                    .iter()
+                    .map(|model| changed_at(model.created_at, model.updated_at, model.deleted_at)),
+            )
+            .max();
+
+        // changes made by other instances, announced to local subscribers once the cache is up to date
+        let mut changes = Vec::new();
+
+        for model in fetched.core.into_iter() {
+            if model.deleted_at > 0 {
+                self.names.remove(&model.id);
+                self.schemas.remove(&model.name);
+                self.base.remove(&model.name);
+                if self.global.remove(&model.name).is_some() {
+                    changes.push(self.synced(ApplicationSettingType::Global, "", &model.name, None));
+                }
+                self.user.retain(|(_, name), _| *name != model.name);
+                self.scoped.retain(|(_, _, name), _| *name != model.name);
+            } else {
+                // validators only live in memory, so carry them over to the reloaded schema
+                let validators = self
+                    .schemas
+                    .get(&model.name)
+                    .map(|schema| schema.validators().to_vec())
+                    .unwrap_or_default();
+                self.names.insert(model.id, model.name.clone());
+                self.schemas.insert(
+                    model.name.clone(),
+                    SettingSchema::from_model(&model).with_validators(&validators),
+                );
+                self.base.insert(model.name.clone(), (model, timestamp));
+            }
+        }
+
+        for model in fetched.global.into_iter() {
+            let name = match self.names.get(&model.setting) {
+                Some(name) => name.clone(),
+                None => continue,
+            };
+
+            let cached = self.cached_value(ApplicationSettingType::Global, &name, None);
+            if model.deleted_at > 0 {
+                if cached.is_some() {
+                    changes.push(self.synced(ApplicationSettingType::Global, "", &name, None));
+                }
+                self.global.remove(&name);
+            } else {
+                if cached.as_deref() != Some(model.value.as_str()) {
+                    changes.push(self.synced(ApplicationSettingType::Global, "", &name, Some(&model.value)));
+                }
+                self.global.insert(name, (model, timestamp));
+            }
+        }
+
+        for model in fetched.user.into_iter() {
+            let name = match self.names.get(&model.setting) {
+                Some(name) => name.clone(),
+                None => continue,
+            };
+
+            // users that are not loaded will get the latest values when they are
+            if !self.users.contains_key(&model.hash_user) {
+                continue;
+            }
+
+            let cached = self.cached_value(ApplicationSettingType::User, &name, Some(&model.hash_user));
+            if model.deleted_at > 0 {
+                if cached.is_some() {
+                    changes.push(self.synced(ApplicationSettingType::User, &model.hash_user, &name, None));
+                }
+                self.user.remove(&(model.hash_user.clone(), name));
+            } else {
+                if cached.as_deref() != Some(model.value.as_str()) {
+                    changes.push(self.synced(
+                        ApplicationSettingType::User,
+                        &model.hash_user,
+                        &name,
+                        Some(&model.value),
+                    ));
+                }
+                self.user.insert((model.hash_user.clone(), name), (model, timestamp));
+            }
+        }
+
+        for model in fetched.scoped.into_iter() {
+            let name = match self.names.get(&model.setting) {
+                Some(name) => name.clone(),
+                None => continue,
+            };
+
+            // same as users, scopes that are not loaded will get the latest values when they are
+            let setting_type = match ApplicationSettingType::from_scope(&model.scope_type) {
+                Some(setting_type) => setting_type,
+                None => continue,
+            };
+            if !self.scopes.contains_key(&(setting_type, model.scope_id.clone())) {
+                continue;
+            }
+
+            let cached = self.cached_value(setting_type, &name, Some(&model.scope_id));
+            if model.deleted_at > 0 {
+                if cached.is_some() {
+                    changes.push(self.synced(setting_type, &model.scope_id, &name, None));
+                }
+                self.scoped.remove(&(setting_type, model.scope_id.clone(), name));
+            } else {
+                if cached.as_deref() != Some(model.value.as_str()) {
+                    changes.push(self.synced(setting_type, &model.scope_id, &name, Some(&model.value)));
+                }
+                self.scoped
+                    .insert((setting_type, model.scope_id.clone(), name), (model, timestamp));
+            }
+        }
+
+        for event in changes.iter() {
+            self.emit(event);
+        }
+
+        self.last_sync = next_sync(self.last_sync, newest);
+    }
+
+    /// unix timestamp the next refresh fetches changes from. Trails the newest change seen in the database by `SYNC_OVERLAP`
+    pub fn last_sync(&self) -> UnixTimestamp {
+        self.last_sync
+    }
+
+    /// share the settings between tasks and refresh them in the background on the supplied interval.
+    /// when the transport supports pub/sub, changes from other instances are applied as soon as they arrive
+    /// and polling continues on the interval to catch anything that was missed.
+    /// the background task stops once every handle to the shared settings has been dropped
+    pub fn auto_refresh(self, interval: Duration) -> (SharedApplicationSettings<Extension>, JoinHandle<()>)
+    where
+        Extension: Send + Sync + 'static,
+    {
+        let mut remote = self.transport.subscribe();
+        let shared = Arc::new(RwLock::new(self));
+        let weak: Weak<RwLock<ApplicationSettings<Extension>>> = Arc::downgrade(&shared);
+
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes right away and we have only just loaded
+            ticker.tick().await;
+
+            loop {
+                let event = match remote.as_mut() {
+                    Some(stream) => tokio::select! {
+                        _ = ticker.tick() => None,
+                        event = stream.next() => Some(event),
+                    },
+                    None => {
+                        ticker.tick().await;
+                        None
+                    }
+                };
+
+                // the transport went away, fall back to polling
+                let event = match event {
+                    Some(None) => {
+                        tracing::warn!("Settings transport closed, falling back to polling the database");
+                        remote = None;
+                        continue;
+                    }
+                    Some(Some(event)) => Some(event),
+                    None => None,
+                };
+
+                let settings = match weak.upgrade() {
+                    Some(settings) => settings,
+                    None => break,
+                };
+
+                match event {
+                    Some(event) => settings.write().await.apply(&event),
+                    None => {
+                        // only hold the write lock while merging so readers are not blocked by the queries
+                        let changes = settings.read().await.changes().await;
+                        match changes {
+                            Ok(changes) => settings.write().await.merge(changes),
+                            Err(err) => tracing::error!("Unable to refresh application settings: {}", err),
+                        }
+                    }
+                }
+            }
+        });
+
+        (shared, handle)
+    }
+
     /// gets a global specific setting value
     /// if there is no matching setting with that name, None is returned.
     pub fn get_global(&self, name: &str) -> Option<String> {
@@ -100,16 +594,180 @@ where
 
     /// gets a user application setting. If no user setting can be found. Falls back to the global setting
     /// if no global setting is found. None is returned
+    ///
+    /// note: this only looks at the cache. Use `get_user_or_load` when the user may not have been loaded yet
     pub fn get_user(&self, user: &str, setting_name: &str) -> Option<String> {
         self.get(ApplicationSettingType::User, setting_name, Some(user.to_string()))
     }
 
+    /// gets a user application setting, loading all of the user's settings first if they are not cached.
+    /// falls back to the global setting the same way `get_user` does
+    pub async fn get_user_or_load(&mut self, user: &str, setting_name: &str) -> anyhow::Result<Option<String>> {
+        if !self.is_user_loaded(user) {
+            self.load_user(user).await?;
+        }
+
+        Ok(self.get_user(user, setting_name))
+    }
+
+    /// gets a global setting and deserializes it into the target type using the registered schema
+    pub fn get_global_as<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<Option<T>> {
+        self.parse(name, self.get_global(name))
+    }
+
+    /// gets a user setting and deserializes it into the target type using the registered schema.
+    /// falls back to the global setting the same way `get_user` does
+    pub fn get_user_as<T: DeserializeOwned>(&self, user: &str, name: &str) -> anyhow::Result<Option<T>> {
+        self.parse(name, self.get_user(user, name))
+    }
+
+    /// deserialize a raw setting value. Settings without a registered type are read as strings
+    fn parse<T: DeserializeOwned>(&self, name: &str, value: Option<String>) -> anyhow::Result<Option<T>> {
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let value_type = self
+            .schemas
+            .get(name)
+            .map_or(&SettingValueType::String, |schema| schema.value_type());
+        value_type
+            .parse(&value)
+            .map(Some)
+            .map_err(|err| anyhow!("Unable to read setting {} as {}: {}", name, value_type, err))
+    }
+
+    /// the registered schema of a setting
+    pub fn schema(&self, name: &str) -> Option<&SettingSchema> {
+        self.schemas.get(name)
+    }
+
+    /// register the schema of a setting, creating the setting if it does not exist yet.
+    /// the type, default, description and secret flag are persisted. Validators only apply to this instance.
+    /// when a setting becomes secret every value already stored for it is encrypted
+    pub async fn register(&mut self, schema: SettingSchema) -> anyhow::Result<()> {
+        if let Some(default) = schema.default_value() {
+            schema.validate(default)?;
+        }
+
+        let becomes_secret = schema.is_secret() && !self.schemas.get(schema.name()).is_some_and(|v| v.is_secret());
+        if schema.is_secret() && self.secret_key.is_none() {
+            return Err(anyhow!(
+                "Setting {} is secret but no settings key has been configured",
+                schema.name()
+            ));
+        }
+
+        let timestamp = unix_timestamp();
+        let model = match self.base.get(schema.name()) {
+            Some((model, _)) => {
+                let mut active: application_settings::ActiveModel = model.clone().into();
+                active.secret = ActiveValue::Set(schema.is_secret());
+                active.value_type = ActiveValue::Set(schema.value_type().as_str().to_string());
+                active.value_options = ActiveValue::Set(schema.value_type().options());
+                active.default_value = ActiveValue::Set(schema.default_value().map(|v| v.to_string()));
+                active.description = ActiveValue::Set(Some(schema.description().to_string()));
+                active.updated_at = ActiveValue::Set(timestamp);
+                active.update(&self.application.state.database_core).await?
+            }
+            None => self.create_setting(schema.name(), Some(&schema)).await?,
+        };
+
+        self.names.insert(model.id, model.name.clone());
+        self.base.insert(model.name.clone(), (model, timestamp));
+        let name = schema.name().to_string();
+        self.schemas.insert(name.clone(), schema);
+
+        // anything that was stored before the setting became secret is still plain text
+        if becomes_secret {
+            self.encrypt_existing(&name).await?;
+        }
+        Ok(())
+    }
+
+    /// insert the core setting row that global and user values point to
+    async fn create_setting(
+        &self,
+        name: &str,
+        schema: Option<&SettingSchema>,
+    ) -> anyhow::Result<application_settings::Model> {
+        let timestamp = unix_timestamp();
+        let seed = format!(
+            "{}|{}|core|{}|{}",
+            timestamp,
+            self.application.record.id,
+            name,
+            Uuid::new_v4()
+        );
+        let hash = format!("{:x}", md5::compute(seed));
+        let value_type = schema.map_or(SettingValueType::String, |schema| schema.value_type().clone());
+
+        // create model
+        let core_model = application_settings::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(self.application.record.id),
+            hash: ActiveValue::Set(hash),
+            name: ActiveValue::Set(name.to_string()),
+            value_type: ActiveValue::Set(value_type.as_str().to_string()),
+            value_options: ActiveValue::Set(value_type.options()),
+            default_value: ActiveValue::Set(schema.and_then(|schema| schema.default_value().map(|v| v.to_string()))),
+            description: ActiveValue::Set(schema.map(|schema| schema.description().to_string())),
+            secret: ActiveValue::Set(schema.is_some_and(|schema| schema.is_secret())),
+            created_at: ActiveValue::Set(timestamp),
+            updated_at: ActiveValue::Set(timestamp),
+            deleted_at: ActiveValue::Set(0),
+        };
+
+        // a concurrent writer or a previously deleted row may already hold the name. Revive it instead of failing
+        let mut revive = vec![
+            application_settings::Column::UpdatedAt,
+            application_settings::Column::DeletedAt,
+        ];
+        if schema.is_some() {
+            revive.extend([
+                application_settings::Column::ValueType,
+                application_settings::Column::ValueOptions,
+                application_settings::Column::DefaultValue,
+                application_settings::Column::Description,
+                application_settings::Column::Secret,
+            ]);
+        }
+
+        application_settings::Entity::insert(core_model)
+            .on_conflict(
+                OnConflict::columns([
+                    application_settings::Column::Application,
+                    application_settings::Column::Name,
+                ])
+                .update_columns(revive)
+                .to_owned(),
+            )
+            .exec_without_returning(&self.application.state.database_core)
+            .await?;
+
+        application_settings::Entity::find()
+            .filter(application_settings::Column::Application.eq(self.application.record.id))
+            .filter(application_settings::Column::Name.eq(name))
+            .one(&self.application.state.database_core)
+            .await?
+            .ok_or_else(|| anyhow!("Unable to create setting {}", name))
+    }
+
     /// get the setting value that is already cached and loaded from our database.
+    /// for guild and channel settings `user` is the guild or channel id. Use `resolve` to go through the full chain.
     /// if a user setting does not exist for the targeted user. The global setting will be passed through instead
-    /// if there is no global setting. **None** will be returned as an Option value.
+    /// if there is no global setting. The registered default is used and otherwise **None** will be returned as an Option value.
     pub fn get(&self, app_type: ApplicationSettingType, name: &str, user: Option<String>) -> Option<String> {
         match app_type {
-            ApplicationSettingType::Global => self.global.get(name).map(|(setting, _)| setting.value.clone()),
+            ApplicationSettingType::Global => self.global.get(name).map_or_else(
+                || {
+                    self.schemas
+                        .get(name)
+                        .and_then(|schema| schema.default_value().map(|v| v.to_string()))
+                },
+                |(setting, _)| self.readable(name, &setting.value),
+            ),
             ApplicationSettingType::User => {
                 let user = user
                     .as_ref()
@@ -117,198 +775,123 @@ where
                 self.user
                     .get(&(user, name.to_string()))
                     .map_or(self.get(ApplicationSettingType::Global, name, None), |(setting, _)| {
-                        Some(setting.value.clone())
+                        self.readable(name, &setting.value)
                     })
             }
+            _ => self
+                .cached_value(app_type, name, user.as_deref())
+                .and_then(|value| self.readable(name, &value))
+                .or_else(|| self.get(ApplicationSettingType::Global, name, None)),
         }
     }
 
-    /// globally set a application setting
-    pub async fn set_global(&mut self, name: &str, value: &str) -> anyhow::Result<JoinHandle<()>> {
-        self.set(ApplicationSettingType::Global, name, value, None).await
+    /// list all global settings that are currently cached as (name, value) pairs sorted by name.
+    /// secret values are decrypted
+    pub fn list_global(&self) -> Vec<(String, String)> {
+        let mut settings = self
+            .global
+            .iter()
+            .filter_map(|(name, (model, _))| self.readable(name, &model.value).map(|value| (name.clone(), value)))
+            .collect::<Vec<_>>();
+        settings.sort();
+        settings
     }
 
-    /// at the application user level, set the application setting
-    pub async fn set_user(&mut self, user: &str, name: &str, value: &str) -> anyhow::Result<JoinHandle<()>> {
-        self.set(ApplicationSettingType::User, name, value, Some(user.to_string()))
+    /// list all settings that have been set for the user as (name, value) pairs sorted by name.
+    /// this always goes to the database. Secret values are decrypted
+    pub async fn list_user(&self, user: &str) -> anyhow::Result<Vec<(String, String)>> {
+        let models = application_user_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_user_settings::Column::Application.eq(self.application.record.id))
+                    .add(application_user_settings::Column::HashUser.eq(user))
+                    .add(application_user_settings::Column::DeletedAt.eq(0)),
+            )
+            .all(&self.application.state.database_core)
+            .await?;
+
+        let mut settings = models
+            .into_iter()
+            .filter_map(|model| {
+                let name = self.names.get(&model.setting)?;
+                self.readable(name, &model.value).map(|value| (name.clone(), value))
+            })
+            .collect::<Vec<_>>();
+        settings.sort();
+        Ok(settings)
+    }
+
+    /// remove a global setting
+    pub async fn delete_global(&mut self, name: &str) -> anyhow::Result<()> {
+        self.delete(ApplicationSettingType::Global, name, None).await
+    }
+
+    /// remove a user setting. The user will fall back to the global setting afterwards
+    pub async fn delete_user(&mut self, user: &str, name: &str) -> anyhow::Result<()> {
+        self.delete(ApplicationSettingType::User, name, Some(user.to_string()))
             .await
     }
 
-    /// update a setting in our cache / database.
-    /// in the event that this setting does not exist. It will auto create it in the database accordingly
-    pub async fn set(
+    /// soft delete a setting value from the cache and the database.
+    /// the removal and its history entry are written in a single transaction
+    pub async fn delete(
         &mut self,
         setting_type: ApplicationSettingType,
         name: &str,
-        value: &str,
         user: Option<String>,
-    ) -> anyhow::Result<JoinHandle<()>> {
-        let timestamp = unix_timestamp();
-        let setting_model = if let Some((model, model_timestamp)) = self.base.get(name) {
-            Some(model.clone())
-        } else {
-            let seed = format!(
-                "{}|{}|core|{}|{}",
-                timestamp,
-                self.application.record.id,
-                name,
-                Uuid::new_v4()
-            );
-            let hash = format!("{:x}", md5::compute(seed));
-
-            // create model
-            let core_model = application_settings::ActiveModel {
-                id: ActiveValue::NotSet,
-                application: ActiveValue::Set(self.application.record.id),
-                hash: ActiveValue::Set(hash),
-                name: ActiveValue::Set(name.to_string()),
-                created_at: ActiveValue::Set(timestamp),
-                updated_at: ActiveValue::Set(0),
-                deleted_at: ActiveValue::Set(0),
-            };
-
-            // insert and fetch
-            let insert = application_settings::Entity::insert(core_model)
-                .exec(&self.application.state.database_core)
-                .await?;
-            let model = application_settings::Entity::find_by_id(insert.last_insert_id)
-                .one(&self.application.state.database_core)
-                .await?;
-
-            if let Some(model) = model.as_ref() {
-                self.base.insert(name.to_string(), (model.clone(), timestamp));
-            }
-
-            model
+    ) -> anyhow::Result<()> {
+        let setting_id = match self.base.get(name) {
+            Some((model, _)) => model.id,
+            None => return Ok(()),
         };
 
-        // map the setting out out of the setting model if possible
-        let setting_id = setting_model.as_ref().map_or(0, |m| m.id);
-
-        // create if neccessary
-        match setting_type {
-            ApplicationSettingType::Global => {
-                let do_create = if let Some((model, _)) = self.global.get(name) {
-                    model.id == 0
-                } else {
-                    true
-                };
-
-                if do_create {
-                    let seed = format!(
-                        "{}|{}|global|{}|{}",
-                        timestamp,
-                        self.application.record.id,
-                        name,
-                        Uuid::new_v4()
-                    );
-                    let hash = format!("{:x}", md5::compute(seed));
-                    let active = application_global_settings::ActiveModel {
-                        id: ActiveValue::NotSet,
-                        application: ActiveValue::Set(self.application.record.id),
-                        hash: ActiveValue::Set(hash),
-                        setting: ActiveValue::Set(setting_id),
-                        value: ActiveValue::Set(value.to_string()),
-                        created_at: ActiveValue::Set(timestamp),
-                        updated_at: ActiveValue::Set(0),
-                        deleted_at: ActiveValue::Set(0),
-                    };
-
-                    let insert = application_global_settings::Entity::insert(active)
-                        .exec(&self.application.state.database_core)
-                        .await?;
-
-                    let model = application_global_settings::Entity::find_by_id(insert.last_insert_id)
-                        .one(&self.application.state.database_core)
-                        .await?;
-
-                    if let Some(model) = model {
-                        self.global
-                            .entry(name.to_string())
-                            .and_modify(|(old_model, model_timestamp)| {
-                                *model_timestamp = timestamp;
-                                old_model.id = model.id;
-                                old_model.hash = model.hash.clone();
-                            })
-                            .or_insert((model, timestamp));
-                    }
-                }
+        if let Some(id) = user.as_ref() {
+            if !self.is_scope_loaded(setting_type, id) {
+                self.load_scope(setting_type, id).await?;
             }
-            ApplicationSettingType::User => {
-                let target_user = user.as_ref().map_or(String::new(), |v| v.clone());
-                let target_key = (target_user.clone(), name.to_string());
+        }
 
-                let do_search = if let Some((model, _)) = self.user.get(&target_key) {
-                    model.id == 0
-                } else {
-                    true
-                };
+        let old_value = self.cached_value(setting_type, name, user.as_deref());
+        let id = match setting_type {
+            ApplicationSettingType::Global => String::new(),
+            _ => user.unwrap_or_default(),
+        };
 
-                let mut target_user_model = None;
-                if do_search {
-                    target_user_model = application_user_settings::Entity::find()
-                        .filter(
-                            Condition::all()
-                                .add(application_user_settings::Column::Application.eq(self.application.record.id))
-                                .add(application_user_settings::Column::Setting.eq(setting_id))
-                                .add(application_user_settings::Column::HashUser.eq(target_user.clone())),
-                        )
-                        .one(&self.application.state.database_core)
-                        .await?;
-                    // if we do have a user model, store in our cache for later
-                    if let Some(target_user_model) = target_user_model.as_ref() {
-                        self.user
-                            .insert(target_key.clone(), (target_user_model.clone(), timestamp));
-                    }
-                }
+        self.uncache(setting_type, &id, name);
+        let event = self.event(setting_type, &id, name, None);
+        self.emit(&event);
 
-                let do_create = target_user_model.is_none();
+        let write = SettingWrite {
+            application: self.application.record.id,
+            setting: setting_id,
+            setting_type,
+            id,
+            actor: self.actor.clone(),
+            old_value,
+            new_value: None,
+        };
+        // queued behind any set that has not been persisted yet
+        self.writes.write(write, event, self.transport.clone()).await
+    }
 
-                if do_create {
-                    let seed = format!(
-                        "{}|{}|user|{}|{}",
-                        timestamp,
-                        self.application.record.id,
-                        name,
-                        Uuid::new_v4()
-                    );
-                    let hash = format!("{:x}", md5::compute(seed));
-
-                    let active = application_user_settings::ActiveModel {
-                        id: ActiveValue::NotSet,
-                        application: ActiveValue::Set(self.application.record.id),
-                        hash: ActiveValue::Set(hash),
-                        hash_user: ActiveValue::Set(target_user.clone()),
-                        setting: ActiveValue::Set(setting_id),
-                        value: ActiveValue::Set(value.to_string()),
-                        created_at: ActiveValue::Set(timestamp),
-                        updated_at: ActiveValue::Set(0),
-                        deleted_at: ActiveValue::Set(0),
-                    };
-
-                    let insert = application_user_settings::Entity::insert(active)
-                        .exec(&self.application.state.database_core)
-                        .await?;
-                    let model = application_user_settings::Entity::find_by_id(insert.last_insert_id)
-                        .one(&self.application.state.database_core)
-                        .await?;
-
-                    if let Some(model) = model {
-                        self.user
-                            .entry(target_key)
-                            .and_modify(|(old_model, model_timestamp)| {
-                                *model_timestamp = timestamp;
-                                old_model.id = model.id;
-                                old_model.hash = model.hash.clone();
-                                old_model.hash_user = model.hash_user.clone();
-                            })
-                            .or_insert((model, timestamp));
-                    }
-                }
-            }
+    /// the value that is cached at exactly this scope. Unlike `get` there is no fallback
+    fn cached_value(&self, setting_type: ApplicationSettingType, name: &str, user: Option<&str>) -> Option<String> {
+        match setting_type {
+            ApplicationSettingType::Global => self.global.get(name).map(|(model, _)| model.value.clone()),
+            ApplicationSettingType::User => self
+                .user
+                .get(&(user.unwrap_or_default().to_string(), name.to_string()))
+                .map(|(model, _)| model.value.clone()),
+            _ => self
+                .scoped
+                .get(&(setting_type, user.unwrap_or_default().to_string(), name.to_string()))
+                .map(|(model, _)| model.value.clone()),
         }
+    }
 
-        // update in cache if possible
+    /// put a value into the cache at exactly this scope. `id` is empty for global settings
+    fn cache(&mut self, setting_type: ApplicationSettingType, setting_id: RecordId, id: &str, name: &str, value: &str) {
+        let timestamp = unix_timestamp();
         match setting_type {
             ApplicationSettingType::Global => {
                 self.global
@@ -320,7 +903,7 @@ where
                     })
                     .or_insert((
                         application_global_settings::Model {
-                            id: 0, // not synced
+                            id: 0,
                             application: self.application.record.id,
                             hash: String::new(),
                             setting: setting_id,
@@ -333,10 +916,8 @@ where
                     ));
             }
             ApplicationSettingType::User => {
-                let target_user = user.as_ref().map_or(String::new(), |v| v.clone());
-                let target_key = (target_user.clone(), name.to_string());
                 self.user
-                    .entry(target_key)
+                    .entry((id.to_string(), name.to_string()))
                     .and_modify(|(model, model_timestamp)| {
                         *model_timestamp = timestamp;
                         model.value = value.to_string();
@@ -347,7 +928,31 @@ where
                             id: 0,
                             application: self.application.record.id,
                             hash: String::new(),
-                            hash_user: target_user.clone(),
+                            hash_user: id.to_string(),
+                            setting: setting_id,
+                            value: value.to_string(),
+                            created_at: timestamp,
+                            updated_at: 0,
+                            deleted_at: 0,
+                        },
+                        timestamp,
+                    ));
+            }
+            _ => {
+                self.scoped
+                    .entry((setting_type, id.to_string(), name.to_string()))
+                    .and_modify(|(model, model_timestamp)| {
+                        *model_timestamp = timestamp;
+                        model.value = value.to_string();
+                        model.updated_at = timestamp;
+                    })
+                    .or_insert((
+                        application_scoped_settings::Model {
+                            id: 0,
+                            application: self.application.record.id,
+                            hash: String::new(),
+                            scope_type: setting_type.as_str().to_string(),
+                            scope_id: id.to_string(),
                             setting: setting_id,
                             value: value.to_string(),
                             created_at: timestamp,
@@ -358,40 +963,212 @@ where
                     ));
             }
         }
+    }
 
-        // update in database now, but fire off into its own task. We don't need to wait for this to occur technically.
-        let db_handle = self.application.state.database.clone();
-        let value_clone = value.to_string();
-        let handle = match setting_type {
+    /// remove a value from the cache at exactly this scope
+    fn uncache(&mut self, setting_type: ApplicationSettingType, id: &str, name: &str) {
+        match setting_type {
             ApplicationSettingType::Global => {
-                let active = self.global.get(name).map(|(m, _)| m.clone());
-
-                tokio::spawn(async move {
-                    if let Some(active) = active {
-                        tracing::info!("Syncing setting: {:?}", active);
-                        let mut active: application_global_settings::ActiveModel = active.into();
-                        active.value = ActiveValue::Set(value_clone);
-                        active.updated_at = ActiveValue::Set(unix_timestamp());
-                        let _ = active.update(&db_handle).await;
-                    }
-                })
+                self.global.remove(name);
             }
             ApplicationSettingType::User => {
-                let target_user = user.as_ref().map_or(String::new(), |v| v.clone());
-                let target_key = (target_user.clone(), name.to_string());
-                let active = self.user.get(&target_key).map(|(m, _)| m.clone());
-
-                tokio::spawn(async move {
-                    if let Some(active) = active {
-                        let mut active: application_user_settings::ActiveModel = active.into();
-                        active.value = ActiveValue::Set(value_clone);
-                        active.updated_at = ActiveValue::Set(unix_timestamp());
-                        let _ = active.update(&db_handle).await;
-                    }
-                })
+                self.user.remove(&(id.to_string(), name.to_string()));
             }
+            _ => {
+                self.scoped.remove(&(setting_type, id.to_string(), name.to_string()));
+            }
+        }
+    }
+
+    /// list the changes made to a setting, newest first. Without a user only the global changes are listed
+    pub async fn history(
+        &self,
+        name: &str,
+        user: Option<&str>,
+        limit: u64,
+    ) -> anyhow::Result<Vec<application_settings_history::Model>> {
+        match user {
+            Some(user) => {
+                self.scope_history(ApplicationSettingType::User, name, user, limit)
+                    .await
+            }
+            None => {
+                self.scope_history(ApplicationSettingType::Global, name, "", limit)
+                    .await
+            }
+        }
+    }
+
+    /// list the changes made to a setting at a single scope, newest first
+    pub async fn scope_history(
+        &self,
+        scope: ApplicationSettingType,
+        name: &str,
+        id: &str,
+        limit: u64,
+    ) -> anyhow::Result<Vec<application_settings_history::Model>> {
+        let setting_id = match self.base.get(name) {
+            Some((model, _)) => model.id,
+            None => return Ok(Vec::new()),
         };
 
+        let hash_user = match scope {
+            ApplicationSettingType::Global => "",
+            _ => id,
+        };
+
+        let revisions = application_settings_history::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_settings_history::Column::Application.eq(self.application.record.id))
+                    .add(application_settings_history::Column::Setting.eq(setting_id))
+                    .add(application_settings_history::Column::Scope.eq(scope.as_str()))
+                    .add(application_settings_history::Column::HashUser.eq(hash_user))
+                    .add(application_settings_history::Column::DeletedAt.eq(0)),
+            )
+            .order_by_desc(application_settings_history::Column::Id)
+            .limit(limit)
+            .all(&self.application.state.database_core)
+            .await?;
+
+        Ok(revisions)
+    }
+
+    /// put a setting back to the value it had before the revision was made.
+    /// the rollback itself is recorded as a new revision
+    pub async fn rollback(&mut self, revision: RecordId) -> anyhow::Result<()> {
+        let revision = application_settings_history::Entity::find_by_id(revision)
+            .filter(application_settings_history::Column::Application.eq(self.application.record.id))
+            .filter(application_settings_history::Column::DeletedAt.eq(0))
+            .one(&self.application.state.database_core)
+            .await?
+            .ok_or_else(|| anyhow!("Unable to find settings revision {}", revision))?;
+
+        let name = self
+            .names
+            .get(&revision.setting)
+            .cloned()
+            .ok_or_else(|| anyhow!("Setting of revision {} no longer exists", revision.id))?;
+
+        let (setting_type, user) = match ApplicationSettingType::from_scope(&revision.scope) {
+            Some(ApplicationSettingType::Global) | None => (ApplicationSettingType::Global, None),
+            Some(setting_type) => (setting_type, Some(revision.hash_user.clone())),
+        };
+
+        match revision.old_value {
+            Some(value) => {
+                let value = self.reveal(&name, &value)?;
+                self.set_sync(setting_type, &name, &value, user).await?
+            }
+            None => self.delete(setting_type, &name, user).await?,
+        }
+
+        Ok(())
+    }
+
+    /// globally set a application setting
+    pub async fn set_global(&mut self, name: &str, value: &str) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        self.set(ApplicationSettingType::Global, name, value, None).await
+    }
+
+    /// at the application user level, set the application setting
+    pub async fn set_user(
+        &mut self,
+        user: &str,
+        name: &str,
+        value: &str,
+    ) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        self.set(ApplicationSettingType::User, name, value, Some(user.to_string()))
+            .await
+    }
+
+    /// update a setting in our cache and persist it in the background.
+    /// in the event that this setting does not exist. It will auto create it in the database accordingly.
+    /// the value and its history entry are written as a single upsert inside a transaction on the core database,
+    /// any error while persisting or publishing the change is returned through the handle.
+    /// writes of this instance, including deletes, are persisted one at a time in the order they were made
+    pub async fn set(
+        &mut self,
+        setting_type: ApplicationSettingType,
+        name: &str,
+        value: &str,
+        user: Option<String>,
+    ) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        if let Some(id) = user.as_ref() {
+            if !self.is_scope_loaded(setting_type, id) {
+                self.load_scope(setting_type, id).await?;
+            }
+        }
+
+        if let Some(schema) = self.schemas.get(name) {
+            schema.validate(value)?;
+        }
+
+        // from here on the value is what gets stored. Encrypted when the setting is secret
+        let value = &self.seal(name, value)?;
+        let old_value = self.cached_value(setting_type, name, user.as_deref());
+        let timestamp = unix_timestamp();
+        let setting_id = if let Some((model, _)) = self.base.get(name) {
+            model.id
+        } else {
+            let model = self.create_setting(name, None).await?;
+            self.names.insert(model.id, name.to_string());
+            self.schemas.insert(name.to_string(), SettingSchema::from_model(&model));
+            self.base.insert(name.to_string(), (model.clone(), timestamp));
+            model.id
+        };
+
+        let id = match setting_type {
+            ApplicationSettingType::Global => String::new(),
+            _ => user.unwrap_or_default(),
+        };
+
+        // update in cache right away. Rows that have not been synced yet have an id of 0
+        self.cache(setting_type, setting_id, &id, name, value);
+        let event = self.event(setting_type, &id, name, Some(value));
+        self.emit(&event);
+
+        // persisted in the background in the order changes are made. We don't need to wait for this to occur technically,
+        // but the handle lets us
+        let write = SettingWrite {
+            application: self.application.record.id,
+            setting: setting_id,
+            setting_type,
+            id,
+            actor: self.actor.clone(),
+            old_value,
+            new_value: Some(value.to_string()),
+        };
+        let done = self.writes.push(write, event, self.transport.clone());
+        let handle = tokio::spawn(async move { done.await.map_err(|_| anyhow!("Settings write queue has stopped"))? });
+
         Ok(handle)
     }
+
+    /// update a setting and wait until it has been persisted
+    pub async fn set_sync(
+        &mut self,
+        setting_type: ApplicationSettingType,
+        name: &str,
+        value: &str,
+        user: Option<String>,
+    ) -> anyhow::Result<()> {
+        self.set(setting_type, name, value, user).await?.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn next_sync_test() {
+        // nothing changed, stay where we were
+        assert_eq!(next_sync(100, None), 100);
+        // trail the newest change by the overlap
+        assert_eq!(next_sync(100, Some(200)), 200 - SYNC_OVERLAP);
+        // never move backwards because of rows fetched again in the overlap
+        assert_eq!(next_sync(100, Some(102)), 100);
+        assert_eq!(changed_at(10, 30, 20), 30);
+    }
 }
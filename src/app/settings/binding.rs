@@ -0,0 +1,88 @@
+use serde::de::DeserializeOwned;
+
+use super::schema::{SettingSchema, SettingValueType};
+use super::{ApplicationSettingType, ApplicationSettings};
+
+/// a rust type that can be stored as a setting value
+pub trait SettingValue: DeserializeOwned {
+    fn value_type() -> SettingValueType;
+
+    /// the raw text that is stored in the database
+    fn to_setting(&self) -> String;
+
+    /// read the raw text back into the type
+    fn from_setting(value: &str) -> anyhow::Result<Self> {
+        Self::value_type().parse(value)
+    }
+}
+
+macro_rules! setting_value {
+    ($value_type:ident => $($ty:ty),+) => {
+        $(
+            impl SettingValue for $ty {
+                fn value_type() -> SettingValueType {
+                    SettingValueType::$value_type
+                }
+
+                fn to_setting(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )+
+    };
+}
+
+setting_value!(Bool => bool);
+setting_value!(Int => i8, i16, i32, i64, u8, u16, u32, u64, usize);
+setting_value!(Float => f32, f64);
+setting_value!(String => String);
+setting_value!(Json => serde_json::Value);
+
+/// a struct whose fields are bound to application settings.
+/// implemented through `#[derive(SettingsBinding)]`, see `levelcrush::macros::SettingsBinding`
+pub trait SettingsBinding: Sized {
+    /// the schema of every field
+    fn schemas() -> Vec<SettingSchema>;
+
+    /// read every field out of the settings. Fields that can be overridden read the user setting when a user is supplied.
+    /// anything that has not been set falls back to the field default
+    fn load_from<Extension: Clone>(
+        settings: &ApplicationSettings<Extension>,
+        user: Option<&str>,
+    ) -> anyhow::Result<Self>;
+
+    /// (setting name, raw value, can be overridden by a user) for every field
+    fn values(&self) -> Vec<(&'static str, String, bool)>;
+}
+
+impl<Extension> ApplicationSettings<Extension>
+where
+    Extension: Clone,
+{
+    /// register the schema of every field of the binding
+    pub async fn register_binding<T: SettingsBinding>(&mut self) -> anyhow::Result<()> {
+        for schema in T::schemas().into_iter() {
+            self.register(schema).await?;
+        }
+        Ok(())
+    }
+
+    /// write every field of the binding and wait for it to be synced.
+    /// when a user is supplied only the fields that can be overridden are written
+    pub async fn save_binding<T: SettingsBinding>(&mut self, binding: &T, user: Option<&str>) -> anyhow::Result<()> {
+        for (name, value, overridable) in binding.values().into_iter() {
+            match user {
+                Some(user) if overridable => {
+                    self.set_sync(ApplicationSettingType::User, name, &value, Some(user.to_string()))
+                        .await?
+                }
+                Some(_) => continue,
+                None => {
+                    self.set_sync(ApplicationSettingType::Global, name, &value, None)
+                        .await?
+                }
+            }
+        }
+        Ok(())
+    }
+}
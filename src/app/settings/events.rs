@@ -0,0 +1,232 @@
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use tokio::sync::broadcast;
+
+use super::{ApplicationSettingType, ApplicationSettings};
+
+/// how many events a subscriber can fall behind before it starts missing them
+pub const EVENT_CAPACITY: usize = 256;
+
+/// a setting value that changed. A value of None means the value was removed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettingEvent {
+    /// the instance that made the change. Empty when the change was picked up by polling the database
+    pub origin: String,
+    pub setting_type: ApplicationSettingType,
+    /// the user, guild or channel id. None for global settings
+    pub id: Option<String>,
+    pub name: String,
+    /// the value as it is stored. Secret values stay encrypted
+    pub value: Option<String>,
+}
+
+/// how changes made by one instance reach the other running instances of the application
+pub trait SettingsTransport: Send + Sync {
+    /// tell the other instances about a change that was persisted by this instance
+    fn publish<'a>(&'a self, event: &'a SettingEvent) -> BoxFuture<'a, anyhow::Result<()>>;
+
+    /// changes made by other instances as they happen.
+    /// None when the transport relies on polling the database instead
+    fn subscribe(&self) -> Option<BoxStream<'static, SettingEvent>>;
+}
+
+/// the default transport. Nothing is published and other instances pick up changes by polling `updated_at` through `refresh`
+#[derive(Clone, Debug, Default)]
+pub struct DatabasePolling;
+
+impl SettingsTransport for DatabasePolling {
+    fn publish<'a>(&'a self, _event: &'a SettingEvent) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> Option<BoxStream<'static, SettingEvent>> {
+        None
+    }
+}
+
+/// in process pub/sub between instances that share this transport.
+/// also serves as the reference for wiring up an external pub/sub backend
+#[derive(Clone, Debug)]
+pub struct BroadcastTransport {
+    sender: broadcast::Sender<SettingEvent>,
+}
+
+impl BroadcastTransport {
+    pub fn new() -> BroadcastTransport {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        BroadcastTransport { sender }
+    }
+}
+
+impl Default for BroadcastTransport {
+    fn default() -> Self {
+        BroadcastTransport::new()
+    }
+}
+
+impl SettingsTransport for BroadcastTransport {
+    fn publish<'a>(&'a self, event: &'a SettingEvent) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            // no receivers is not an error, there is simply nobody else running
+            let _ = self.sender.send(event.clone());
+            Ok(())
+        })
+    }
+
+    fn subscribe(&self) -> Option<BoxStream<'static, SettingEvent>> {
+        let receiver = self.sender.subscribe();
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Settings transport fell behind and missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Some(Box::pin(stream))
+    }
+}
+
+/// changes to settings whose name starts with a prefix
+pub struct SettingSubscription {
+    prefix: String,
+    receiver: broadcast::Receiver<SettingEvent>,
+}
+
+impl SettingSubscription {
+    /// wait for the next matching change. None once the settings have been dropped
+    pub async fn recv(&mut self) -> Option<SettingEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.name.starts_with(&self.prefix) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Settings subscription fell behind and missed {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl<Extension> ApplicationSettings<Extension>
+where
+    Extension: Clone,
+{
+    /// get notified about changes to a setting, or every setting that starts with the prefix.
+    /// an empty prefix subscribes to every change
+    pub fn subscribe(&self, prefix: &str) -> SettingSubscription {
+        SettingSubscription {
+            prefix: prefix.to_string(),
+            receiver: self.events.subscribe(),
+        }
+    }
+
+    /// an event for a change made through this instance. `id` is empty for global settings
+    pub(super) fn event(
+        &self,
+        setting_type: ApplicationSettingType,
+        id: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> SettingEvent {
+        SettingEvent {
+            origin: self.origin.clone(),
+            setting_type,
+            id: Some(id.to_string()).filter(|id| !id.is_empty()),
+            name: name.to_string(),
+            value: value.map(|v| v.to_string()),
+        }
+    }
+
+    /// an event for a change that was picked up from the database. The instance that made it is not known
+    pub(super) fn synced(
+        &self,
+        setting_type: ApplicationSettingType,
+        id: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> SettingEvent {
+        SettingEvent {
+            origin: String::new(),
+            ..self.event(setting_type, id, name, value)
+        }
+    }
+
+    /// notify local subscribers. Having no subscribers is fine
+    pub(super) fn emit(&self, event: &SettingEvent) {
+        let _ = self.events.send(event.clone());
+    }
+
+    /// apply a change made by another instance to the cache and notify local subscribers.
+    /// values of users and scopes that are not loaded are ignored, they will be read when loaded
+    pub fn apply(&mut self, event: &SettingEvent) {
+        if event.origin == self.origin {
+            return;
+        }
+
+        let setting_id = match self.base.get(&event.name) {
+            Some((model, _)) => model.id,
+            // a setting we have never seen. The next refresh will pick it up
+            None => return,
+        };
+
+        let id = event.id.clone().unwrap_or_default();
+        if event.setting_type != ApplicationSettingType::Global && !self.is_scope_loaded(event.setting_type, &id) {
+            return;
+        }
+
+        match event.value.as_deref() {
+            Some(value) => self.cache(event.setting_type, setting_id, &id, &event.name, value),
+            None => self.uncache(event.setting_type, &id, &event.name),
+        }
+
+        self.emit(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn event(name: &str) -> SettingEvent {
+        SettingEvent {
+            origin: "mock".to_string(),
+            setting_type: ApplicationSettingType::Global,
+            id: None,
+            name: name.to_string(),
+            value: Some("1".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn setting_subscription_test() -> anyhow::Result<()> {
+        let transport = BroadcastTransport::new();
+        let mut remote = transport.subscribe().expect("Broadcast transport did not subscribe");
+
+        let (sender, receiver) = broadcast::channel(EVENT_CAPACITY);
+        let mut subscription = SettingSubscription {
+            prefix: "mock.a.".to_string(),
+            receiver,
+        };
+
+        for name in ["mock.b.value", "mock.a.value"] {
+            transport.publish(&event(name)).await?;
+            sender.send(event(name))?;
+        }
+        drop(sender);
+
+        assert_eq!(remote.next().await.map(|v| v.name), Some("mock.b.value".to_string()));
+        assert_eq!(
+            subscription.recv().await.map(|v| v.name),
+            Some("mock.a.value".to_string())
+        );
+        assert_eq!(subscription.recv().await, None);
+        Ok(())
+    }
+}
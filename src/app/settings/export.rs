@@ -0,0 +1,478 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+
+use super::schema::{SettingSchema, SettingValueType};
+use super::secret::REDACTED;
+use super::{ApplicationSettingType, ApplicationSettings};
+use crate::entities::{application_scoped_settings, application_user_settings};
+
+/// scope id => setting name => value
+pub type ScopedValues = BTreeMap<String, BTreeMap<String, String>>;
+
+/// every setting definition, global value and optionally user, guild and channel value of an application.
+/// secret values are never exported, they are replaced by `REDACTED`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SettingsExport {
+    #[serde(default)]
+    pub definitions: Vec<SettingDefinition>,
+
+    /// setting name => value
+    #[serde(default)]
+    pub global: BTreeMap<String, String>,
+
+    /// user => setting name => value
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub users: ScopedValues,
+
+    /// guild => setting name => value
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub guilds: ScopedValues,
+
+    /// channel => setting name => value
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub channels: ScopedValues,
+}
+
+/// the stored schema of a setting
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SettingDefinition {
+    pub name: String,
+    #[serde(rename = "type", default = "default_value_type")]
+    pub value_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+fn default_value_type() -> String {
+    SettingValueType::String.as_str().to_string()
+}
+
+impl SettingDefinition {
+    fn from_schema(schema: &SettingSchema) -> SettingDefinition {
+        let options = match schema.value_type() {
+            SettingValueType::Enum(options) => options.clone(),
+            _ => Vec::new(),
+        };
+
+        SettingDefinition {
+            name: schema.name().to_string(),
+            value_type: schema.value_type().as_str().to_string(),
+            options,
+            default: schema.default_value().map(|v| v.to_string()),
+            description: Some(schema.description().to_string()).filter(|v| !v.is_empty()),
+            secret: schema.is_secret(),
+        }
+    }
+
+    fn value_type(&self) -> SettingValueType {
+        match self.value_type.as_str() {
+            "enum" => SettingValueType::Enum(self.options.clone()),
+            value_type => SettingValueType::from_parts(value_type, None),
+        }
+    }
+
+    fn to_schema(&self) -> SettingSchema {
+        let mut schema = SettingSchema::new(&self.name, self.value_type());
+        if let Some(default) = self.default.as_ref() {
+            schema = schema.with_default(default);
+        }
+        if let Some(description) = self.description.as_ref() {
+            schema = schema.with_description(description);
+        }
+        if self.secret {
+            schema = schema.with_secret();
+        }
+        schema
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Yaml,
+    Json,
+}
+
+impl ExportFormat {
+    /// guess the format from a file name. Anything that is not json is treated as yaml
+    pub fn from_path(path: &str) -> ExportFormat {
+        if path.to_lowercase().ends_with(".json") {
+            ExportFormat::Json
+        } else {
+            ExportFormat::Yaml
+        }
+    }
+}
+
+impl SettingsExport {
+    pub fn to_string(&self, format: ExportFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ExportFormat::Yaml => serde_yaml::to_string(self)?,
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    pub fn parse(input: &str, format: ExportFormat) -> anyhow::Result<SettingsExport> {
+        Ok(match format {
+            ExportFormat::Yaml => serde_yaml::from_str(input)?,
+            ExportFormat::Json => serde_json::from_str(input)?,
+        })
+    }
+}
+
+/// how values that already exist are treated on import
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// values in the file overwrite existing values. Anything not in the file is left alone
+    Merge,
+    /// the file becomes the source of truth. Global values, and the values of any user, guild or channel in the file, that are not in the file are removed
+    Replace,
+    /// only values that do not exist yet are set
+    Skip,
+}
+
+/// a single change an import makes. An old value of None is a new setting, a new value of None is a removal
+#[derive(Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    pub setting_type: ApplicationSettingType,
+    /// the user, guild or channel the value is set on. None for global values
+    pub user: Option<String>,
+    pub name: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub secret: bool,
+}
+
+impl SettingChange {
+    /// the old and new value as they are safe to display
+    pub fn display_values(&self) -> (Option<&str>, Option<&str>) {
+        (self.display(&self.old_value), self.display(&self.new_value))
+    }
+
+    fn display<'a>(&self, value: &'a Option<String>) -> Option<&'a str> {
+        match self.secret {
+            true => value.as_ref().map(|_| REDACTED),
+            false => value.as_deref(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SettingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (old_value, new_value) = self.display_values();
+        f.debug_struct("SettingChange")
+            .field("setting_type", &self.setting_type)
+            .field("user", &self.user)
+            .field("name", &self.name)
+            .field("old_value", &old_value)
+            .field("new_value", &new_value)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+/// compare the current values of a single scope against the values in the file
+fn diff_scope(
+    setting_type: ApplicationSettingType,
+    user: Option<&str>,
+    current: &BTreeMap<String, String>,
+    incoming: &BTreeMap<String, String>,
+    mode: ImportMode,
+) -> Vec<SettingChange> {
+    let change = |name: &str, old_value: Option<&String>, new_value: Option<&String>| SettingChange {
+        setting_type,
+        user: user.map(|v| v.to_string()),
+        name: name.to_string(),
+        old_value: old_value.cloned(),
+        new_value: new_value.cloned(),
+        secret: false,
+    };
+
+    let mut changes = incoming
+        .iter()
+        .filter_map(|(name, value)| match current.get(name) {
+            None => Some(change(name, None, Some(value))),
+            Some(_) if mode == ImportMode::Skip => None,
+            Some(old_value) if old_value == value => None,
+            Some(old_value) => Some(change(name, Some(old_value), Some(value))),
+        })
+        .collect::<Vec<_>>();
+
+    if mode == ImportMode::Replace {
+        changes.extend(
+            current
+                .iter()
+                .filter(|(name, _)| !incoming.contains_key(*name))
+                .map(|(name, old_value)| change(name, Some(old_value), None)),
+        );
+    }
+
+    changes
+}
+
+impl<Extension> ApplicationSettings<Extension>
+where
+    Extension: Clone,
+{
+    /// dump every setting definition and global value. User, guild and channel values are read from the database when requested
+    pub async fn export(&self, include_scoped: bool) -> anyhow::Result<SettingsExport> {
+        let mut definitions = self
+            .schemas
+            .values()
+            .map(SettingDefinition::from_schema)
+            .collect::<Vec<_>>();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let redact = |name: &str, value: String| match self.is_secret(name) {
+            true => REDACTED.to_string(),
+            false => value,
+        };
+
+        let global = self
+            .list_global()
+            .into_iter()
+            .map(|(name, value)| {
+                let value = redact(&name, value);
+                (name, value)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut users = BTreeMap::new();
+        let mut guilds = BTreeMap::new();
+        let mut channels = BTreeMap::new();
+        if include_scoped {
+            let models = application_user_settings::Entity::find()
+                .filter(
+                    Condition::all()
+                        .add(application_user_settings::Column::Application.eq(self.application.record.id))
+                        .add(application_user_settings::Column::DeletedAt.eq(0)),
+                )
+                .all(&self.application.state.database_core)
+                .await?;
+
+            for model in models.into_iter() {
+                if let Some(name) = self.names.get(&model.setting) {
+                    users
+                        .entry(model.hash_user)
+                        .or_insert_with(BTreeMap::new)
+                        .insert(name.clone(), redact(name, model.value));
+                }
+            }
+
+            let models = application_scoped_settings::Entity::find()
+                .filter(
+                    Condition::all()
+                        .add(application_scoped_settings::Column::Application.eq(self.application.record.id))
+                        .add(application_scoped_settings::Column::DeletedAt.eq(0)),
+                )
+                .all(&self.application.state.database_core)
+                .await?;
+
+            for model in models.into_iter() {
+                let scoped = match ApplicationSettingType::from_scope(&model.scope_type) {
+                    Some(ApplicationSettingType::Guild) => &mut guilds,
+                    Some(ApplicationSettingType::Channel) => &mut channels,
+                    _ => continue,
+                };
+                if let Some(name) = self.names.get(&model.setting) {
+                    scoped
+                        .entry(model.scope_id)
+                        .or_insert_with(BTreeMap::new)
+                        .insert(name.clone(), redact(name, model.value));
+                }
+            }
+        }
+
+        Ok(SettingsExport {
+            definitions,
+            global,
+            users,
+            guilds,
+            channels,
+        })
+    }
+
+    /// work out what an import would change without changing anything.
+    /// every incoming value is validated against the incoming definition or the registered schema.
+    /// redacted secret values are left as they are
+    pub async fn diff(&mut self, export: &SettingsExport, mode: ImportMode) -> anyhow::Result<Vec<SettingChange>> {
+        let definitions = export
+            .definitions
+            .iter()
+            .map(|definition| (definition.name.as_str(), definition.to_schema()))
+            .collect::<BTreeMap<_, _>>();
+
+        let incoming = std::iter::once(&export.global)
+            .chain(export.users.values())
+            .chain(export.guilds.values())
+            .chain(export.channels.values());
+        for values in incoming {
+            for (name, value) in values.iter() {
+                let schema = definitions.get(name.as_str()).or_else(|| self.schemas.get(name));
+                if value == REDACTED && schema.is_some_and(|schema| schema.is_secret()) {
+                    continue;
+                }
+                if let Some(schema) = schema {
+                    schema.validate(value)?;
+                }
+            }
+        }
+
+        let current = self.list_global().into_iter().collect::<BTreeMap<_, _>>();
+        let mut changes = diff_scope(ApplicationSettingType::Global, None, &current, &export.global, mode);
+
+        let scoped = [
+            (ApplicationSettingType::User, &export.users),
+            (ApplicationSettingType::Guild, &export.guilds),
+            (ApplicationSettingType::Channel, &export.channels),
+        ];
+        for (setting_type, scopes) in scoped.into_iter() {
+            for (id, values) in scopes.iter() {
+                let current = self
+                    .list_scope(setting_type, id)
+                    .await?
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>();
+                changes.extend(diff_scope(setting_type, Some(id), &current, values, mode));
+            }
+        }
+
+        let secret = |name: &str| {
+            definitions
+                .get(name)
+                .or_else(|| self.schemas.get(name))
+                .is_some_and(|schema| schema.is_secret())
+        };
+        let changes = changes
+            .into_iter()
+            .map(|change| SettingChange {
+                secret: secret(&change.name),
+                ..change
+            })
+            .filter(|change| !(change.secret && change.new_value.as_deref() == Some(REDACTED)))
+            .collect();
+
+        Ok(changes)
+    }
+
+    /// apply an export to these settings. With `dry_run` nothing is written and the changes that would be made are returned
+    pub async fn import(
+        &mut self,
+        export: &SettingsExport,
+        mode: ImportMode,
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<SettingChange>> {
+        let changes = self.diff(export, mode).await?;
+        if dry_run {
+            return Ok(changes);
+        }
+
+        for definition in export.definitions.iter() {
+            if mode == ImportMode::Skip && self.schemas.contains_key(&definition.name) {
+                continue;
+            }
+
+            // keep any validators that have been registered on this instance
+            let validators = self
+                .schemas
+                .get(&definition.name)
+                .map(|schema| schema.validators().to_vec())
+                .unwrap_or_default();
+            self.register(definition.to_schema().with_validators(&validators))
+                .await?;
+        }
+
+        for change in changes.iter() {
+            match change.new_value.as_ref() {
+                Some(value) => {
+                    self.set_sync(change.setting_type, &change.name, value, change.user.clone())
+                        .await?
+                }
+                None => {
+                    self.delete(change.setting_type, &change.name, change.user.clone())
+                        .await?
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+impl std::str::FromStr for ImportMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            "skip" => Ok(ImportMode::Skip),
+            _ => Err(anyhow!("Unknown import mode {}", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn export_roundtrip_test() -> anyhow::Result<()> {
+        let mut export = SettingsExport::default();
+        export.definitions.push(SettingDefinition {
+            name: "mock.mode".to_string(),
+            value_type: "enum".to_string(),
+            options: vec!["fast".to_string(), "slow".to_string()],
+            default: Some("fast".to_string()),
+            description: None,
+            secret: false,
+        });
+        export.global.insert("mock.mode".to_string(), "slow".to_string());
+        export.guilds.insert(
+            "42".to_string(),
+            BTreeMap::from([("mock.mode".to_string(), "fast".to_string())]),
+        );
+
+        for format in [ExportFormat::Yaml, ExportFormat::Json] {
+            let output = export.to_string(format)?;
+            assert_eq!(SettingsExport::parse(&output, format)?, export);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    pub fn diff_scope_test() {
+        let current = BTreeMap::from([("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+        let incoming = BTreeMap::from([("a".to_string(), "10".to_string()), ("c".to_string(), "3".to_string())]);
+
+        let diff = |mode| diff_scope(ApplicationSettingType::Global, None, &current, &incoming, mode);
+        let values = |changes: Vec<SettingChange>| {
+            changes
+                .into_iter()
+                .map(|change| (change.name, change.new_value))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            values(diff(ImportMode::Merge)),
+            vec![
+                ("a".to_string(), Some("10".to_string())),
+                ("c".to_string(), Some("3".to_string()))
+            ]
+        );
+        assert_eq!(
+            values(diff(ImportMode::Skip)),
+            vec![("c".to_string(), Some("3".to_string()))]
+        );
+        assert_eq!(values(diff(ImportMode::Replace)).last(), Some(&("b".to_string(), None)));
+    }
+}
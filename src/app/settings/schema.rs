@@ -0,0 +1,255 @@
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+
+use super::secret::REDACTED;
+use crate::entities::application_settings;
+
+/// custom validation that runs after the value has passed the type check
+pub type SettingValidator = Arc<dyn Fn(&str) -> anyhow::Result<()> + Send + Sync>;
+
+/// the type of value a setting holds. Values are always stored as text in the database
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SettingValueType {
+    Bool,
+    Int,
+    Float,
+    String,
+    /// one of the supplied options
+    Enum(Vec<String>),
+    Json,
+}
+
+impl SettingValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettingValueType::Bool => "bool",
+            SettingValueType::Int => "int",
+            SettingValueType::Float => "float",
+            SettingValueType::String => "string",
+            SettingValueType::Enum(_) => "enum",
+            SettingValueType::Json => "json",
+        }
+    }
+
+    /// rebuild the value type from the database columns. Unknown types are treated as strings
+    pub fn from_parts(value_type: &str, options: Option<&str>) -> SettingValueType {
+        match value_type {
+            "bool" => SettingValueType::Bool,
+            "int" => SettingValueType::Int,
+            "float" => SettingValueType::Float,
+            "json" => SettingValueType::Json,
+            "enum" => SettingValueType::Enum(
+                options
+                    .and_then(|options| serde_json::from_str::<Vec<String>>(options).ok())
+                    .unwrap_or_default(),
+            ),
+            _ => SettingValueType::String,
+        }
+    }
+
+    /// the options of an enum encoded as a json array. None for every other type
+    pub fn options(&self) -> Option<String> {
+        match self {
+            SettingValueType::Enum(options) => serde_json::to_string(options).ok(),
+            _ => None,
+        }
+    }
+
+    /// checks that the raw value can be read as this type
+    pub fn check(&self, value: &str) -> anyhow::Result<()> {
+        let valid = match self {
+            SettingValueType::Bool => value.parse::<bool>().is_ok(),
+            SettingValueType::Int => value.parse::<i64>().is_ok(),
+            SettingValueType::Float => value.parse::<f64>().is_ok(),
+            SettingValueType::String => true,
+            SettingValueType::Enum(options) => options.iter().any(|option| option == value),
+            SettingValueType::Json => serde_json::from_str::<serde_json::Value>(value).is_ok(),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(anyhow!("'{}' is not a valid {} value", value, self.as_str()))
+        }
+    }
+
+    /// deserialize the raw value into the target type.
+    /// strings and enums are stored unquoted so they are wrapped before being handed to serde
+    pub fn parse<T: DeserializeOwned>(&self, value: &str) -> anyhow::Result<T> {
+        let parsed = match self {
+            SettingValueType::String | SettingValueType::Enum(_) => {
+                serde_json::from_value(serde_json::Value::String(value.to_string()))?
+            }
+            _ => serde_json::from_str(value)?,
+        };
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for SettingValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// describes what a setting is allowed to hold
+#[derive(Clone)]
+pub struct SettingSchema {
+    name: String,
+    value_type: SettingValueType,
+    default: Option<String>,
+    description: String,
+    secret: bool,
+    validators: Vec<SettingValidator>,
+}
+
+impl SettingSchema {
+    pub fn new(name: &str, value_type: SettingValueType) -> SettingSchema {
+        SettingSchema {
+            name: name.to_string(),
+            value_type,
+            default: None,
+            description: String::new(),
+            secret: false,
+            validators: Vec::new(),
+        }
+    }
+
+    /// rebuild the schema from the stored setting. Validators only exist in memory and are not restored
+    pub fn from_model(model: &application_settings::Model) -> SettingSchema {
+        SettingSchema {
+            name: model.name.clone(),
+            value_type: SettingValueType::from_parts(&model.value_type, model.value_options.as_deref()),
+            default: model.default_value.clone(),
+            description: model.description.clone().unwrap_or_default(),
+            secret: model.secret,
+            validators: Vec::new(),
+        }
+    }
+
+    /// value returned when the setting has not been set
+    pub fn with_default(mut self, value: &str) -> Self {
+        self.default = Some(value.to_string());
+        self
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// encrypt the value at rest and keep it out of exports, logs and debug output
+    pub fn with_secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    /// add a custom validator. Validators run in the order they were added
+    pub fn with_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    /// keep the validators of another schema. Used when the stored schema is reloaded
+    pub(crate) fn with_validators(mut self, validators: &[SettingValidator]) -> Self {
+        self.validators = validators.to_vec();
+        self
+    }
+
+    pub(crate) fn validators(&self) -> &[SettingValidator] {
+        &self.validators
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value_type(&self) -> &SettingValueType {
+        &self.value_type
+    }
+
+    pub fn default_value(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.secret
+    }
+
+    /// run the type check and then every validator against the value.
+    /// errors of secret settings never include the reason, it may contain the value
+    pub fn validate(&self, value: &str) -> anyhow::Result<()> {
+        let invalid = |err: anyhow::Error| match self.secret {
+            true => anyhow!("Invalid value for secret setting {}", self.name),
+            false => anyhow!("Invalid value for setting {}: {}", self.name, err),
+        };
+
+        self.value_type.check(value).map_err(invalid)?;
+        for validator in self.validators.iter() {
+            validator(value).map_err(invalid)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SettingSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SettingSchema")
+            .field("name", &self.name)
+            .field("value_type", &self.value_type)
+            .field(
+                "default",
+                &self.default.as_ref().map(|v| if self.secret { REDACTED } else { v }),
+            )
+            .field("description", &self.description)
+            .field("secret", &self.secret)
+            .field("validators", &self.validators.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn schema_validate_test() {
+        let schema = SettingSchema::new("mock.limit", SettingValueType::Int)
+            .with_default("10")
+            .with_validator(|value| match value.parse::<i64>()? {
+                1..=100 => Ok(()),
+                _ => Err(anyhow!("must be between 1 and 100")),
+            });
+
+        assert!(schema.validate("50").is_ok());
+        assert!(schema.validate("500").is_err());
+        assert!(schema.validate("abc").is_err());
+
+        let mode = SettingValueType::Enum(vec!["fast".to_string(), "slow".to_string()]);
+        assert!(mode.check("fast").is_ok());
+        assert!(mode.check("medium").is_err());
+        assert_eq!(SettingValueType::from_parts("enum", mode.options().as_deref()), mode);
+
+        assert!(SettingValueType::Bool.parse::<bool>("true").unwrap());
+        assert_eq!(mode.parse::<String>("slow").unwrap(), "slow");
+        assert_eq!(SettingValueType::Json.parse::<Vec<i64>>("[1,2]").unwrap(), vec![1, 2]);
+
+        let secret = SettingSchema::new("mock.token", SettingValueType::Int)
+            .with_default("1234")
+            .with_secret();
+        let err = secret.validate("hunter2").unwrap_err();
+        assert!(!err.to_string().contains("hunter2"));
+        assert!(!format!("{:?}", secret).contains("1234"));
+    }
+}
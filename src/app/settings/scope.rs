@@ -0,0 +1,234 @@
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter};
+use tokio::task::JoinHandle;
+
+use super::{ApplicationSettingType, ApplicationSettings};
+use crate::{entities::application_scoped_settings, util::unix_timestamp};
+
+/// the ids that make up the resolution chain of a setting. Any scope left as None is skipped
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SettingScopes {
+    pub user: Option<String>,
+    pub channel: Option<String>,
+    pub guild: Option<String>,
+}
+
+impl SettingScopes {
+    pub fn new() -> SettingScopes {
+        SettingScopes::default()
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    pub fn channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    pub fn guild(mut self, guild: &str) -> Self {
+        self.guild = Some(guild.to_string());
+        self
+    }
+
+    /// (scope, id) pairs in the order they are resolved. Global is always last
+    pub fn chain(&self) -> Vec<(ApplicationSettingType, Option<&str>)> {
+        let mut chain = Vec::new();
+        if let Some(user) = self.user.as_deref() {
+            chain.push((ApplicationSettingType::User, Some(user)));
+        }
+        if let Some(channel) = self.channel.as_deref() {
+            chain.push((ApplicationSettingType::Channel, Some(channel)));
+        }
+        if let Some(guild) = self.guild.as_deref() {
+            chain.push((ApplicationSettingType::Guild, Some(guild)));
+        }
+        chain.push((ApplicationSettingType::Global, None));
+        chain
+    }
+}
+
+/// the level of the resolution chain that supplied a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingSource {
+    Scope(ApplicationSettingType),
+    /// the default of the registered schema
+    Default,
+}
+
+/// a setting value and where it came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedSetting {
+    pub value: String,
+    pub source: SettingSource,
+}
+
+impl<Extension> ApplicationSettings<Extension>
+where
+    Extension: Clone,
+{
+    /// resolve a setting through user -> channel -> guild -> global -> default using only what is cached
+    pub fn resolve(&self, name: &str, scopes: &SettingScopes) -> Option<ResolvedSetting> {
+        let value = scopes.chain().into_iter().find_map(|(setting_type, id)| {
+            self.cached_value(setting_type, name, id)
+                .and_then(|value| self.readable(name, &value))
+                .map(|value| ResolvedSetting {
+                    value,
+                    source: SettingSource::Scope(setting_type),
+                })
+        });
+
+        value.or_else(|| {
+            self.schemas
+                .get(name)
+                .and_then(|schema| schema.default_value())
+                .map(|value| ResolvedSetting {
+                    value: value.to_string(),
+                    source: SettingSource::Default,
+                })
+        })
+    }
+
+    /// resolve a setting, loading any scope of the chain that is not cached yet
+    pub async fn resolve_or_load(
+        &mut self,
+        name: &str,
+        scopes: &SettingScopes,
+    ) -> anyhow::Result<Option<ResolvedSetting>> {
+        for (setting_type, id) in scopes.chain().into_iter() {
+            if let Some(id) = id {
+                if !self.is_scope_loaded(setting_type, id) {
+                    self.load_scope(setting_type, id).await?;
+                }
+            }
+        }
+
+        Ok(self.resolve(name, scopes))
+    }
+
+    /// checks if every setting of the scope is cached and has not expired. Global settings are always loaded
+    pub fn is_scope_loaded(&self, setting_type: ApplicationSettingType, id: &str) -> bool {
+        match setting_type {
+            ApplicationSettingType::Global => true,
+            ApplicationSettingType::User => self.is_user_loaded(id),
+            _ => self
+                .scopes
+                .get(&(setting_type, id.to_string()))
+                .is_some_and(|loaded_at| unix_timestamp() - loaded_at <= self.user_duration.i64()),
+        }
+    }
+
+    /// load all settings of a single scope into the cache in one query.
+    /// guild and channel scopes share the limit and duration of the user cache
+    pub async fn load_scope(&mut self, setting_type: ApplicationSettingType, id: &str) -> anyhow::Result<()> {
+        match setting_type {
+            ApplicationSettingType::Global => return Ok(()),
+            ApplicationSettingType::User => return self.load_user(id).await,
+            _ => {}
+        }
+
+        let timestamp = unix_timestamp();
+        let models = application_scoped_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_scoped_settings::Column::Application.eq(self.application.record.id))
+                    .add(application_scoped_settings::Column::ScopeType.eq(setting_type.as_str()))
+                    .add(application_scoped_settings::Column::ScopeId.eq(id))
+                    .add(application_scoped_settings::Column::DeletedAt.eq(0)),
+            )
+            .all(&self.application.state.database_core)
+            .await?;
+
+        self.scoped
+            .retain(|(cached_type, cached_id, _), _| !(*cached_type == setting_type && cached_id == id));
+        for model in models.into_iter() {
+            if let Some(name) = self.names.get(&model.setting) {
+                self.scoped
+                    .insert((setting_type, id.to_string(), name.clone()), (model, timestamp));
+            }
+        }
+
+        self.scopes.insert((setting_type, id.to_string()), timestamp);
+        self.prune_users();
+        Ok(())
+    }
+
+    /// list every value set directly on a scope, straight from the database. Nothing falls back to a wider scope
+    pub async fn list_scope(
+        &self,
+        setting_type: ApplicationSettingType,
+        id: &str,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        match setting_type {
+            ApplicationSettingType::Global => return Ok(self.list_global()),
+            ApplicationSettingType::User => return self.list_user(id).await,
+            _ => {}
+        }
+
+        let models = application_scoped_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_scoped_settings::Column::Application.eq(self.application.record.id))
+                    .add(application_scoped_settings::Column::ScopeType.eq(setting_type.as_str()))
+                    .add(application_scoped_settings::Column::ScopeId.eq(id))
+                    .add(application_scoped_settings::Column::DeletedAt.eq(0)),
+            )
+            .all(&self.application.state.database_core)
+            .await?;
+
+        let mut settings = models
+            .into_iter()
+            .filter_map(|model| {
+                let name = self.names.get(&model.setting)?;
+                self.readable(name, &model.value).map(|value| (name.clone(), value))
+            })
+            .collect::<Vec<_>>();
+        settings.sort();
+        Ok(settings)
+    }
+
+    /// set the application setting for a guild
+    pub async fn set_guild(
+        &mut self,
+        guild: &str,
+        name: &str,
+        value: &str,
+    ) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        self.set(ApplicationSettingType::Guild, name, value, Some(guild.to_string()))
+            .await
+    }
+
+    /// set the application setting for a channel
+    pub async fn set_channel(
+        &mut self,
+        channel: &str,
+        name: &str,
+        value: &str,
+    ) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+        self.set(ApplicationSettingType::Channel, name, value, Some(channel.to_string()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn scope_chain_test() {
+        let scopes = SettingScopes::new().guild("g").user("u");
+        assert_eq!(
+            scopes.chain(),
+            vec![
+                (ApplicationSettingType::User, Some("u")),
+                (ApplicationSettingType::Guild, Some("g")),
+                (ApplicationSettingType::Global, None),
+            ]
+        );
+        assert_eq!(
+            SettingScopes::new().chain(),
+            vec![(ApplicationSettingType::Global, None)]
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter, TransactionTrait};
+
+use super::ApplicationSettings;
+use crate::{
+    alias::RecordId,
+    entities::{
+        application_global_settings, application_scoped_settings, application_settings_history,
+        application_user_settings,
+    },
+    env::{self, EnvVar},
+};
+
+/// stands in for the value of a secret setting in exports, logs and debug output
+pub const REDACTED: &str = "[redacted]";
+
+/// marks a stored value as encrypted. Anything without it is plain text
+const PREFIX: &str = "enc:";
+
+/// bytes of the random nonce stored in front of every encrypted value
+const NONCE_LEN: usize = 12;
+
+/// the AES-256-GCM key secret setting values are encrypted with at rest
+#[derive(Clone)]
+pub struct SecretKey {
+    key: Key<Aes256Gcm>,
+}
+
+impl SecretKey {
+    /// read a base64 encoded 32 byte key
+    pub fn from_base64(encoded: &str) -> anyhow::Result<SecretKey> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|err| anyhow!("Settings key is not valid base64: {}", err))?;
+        if bytes.len() != 32 {
+            return Err(anyhow!("Settings key must be 32 bytes, got {}", bytes.len()));
+        }
+
+        Ok(SecretKey {
+            key: *Key::<Aes256Gcm>::from_slice(&bytes),
+        })
+    }
+
+    /// read the key out of the environment variable. None when the variable is not set or empty
+    pub fn from_env(env_var: EnvVar) -> anyhow::Result<Option<SecretKey>> {
+        let encoded = env::get(env_var);
+        if encoded.trim().is_empty() {
+            return Ok(None);
+        }
+        SecretKey::from_base64(&encoded).map(Some)
+    }
+
+    /// a new random key
+    pub fn generate() -> SecretKey {
+        SecretKey {
+            key: Aes256Gcm::generate_key(OsRng),
+        }
+    }
+
+    /// the key as base64, ready to be put into the environment
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.key)
+    }
+
+    pub fn encrypt(&self, value: &str) -> anyhow::Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted = Aes256Gcm::new(&self.key)
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| anyhow!("Unable to encrypt setting value"))?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.extend(encrypted);
+        Ok(format!("{}{}", PREFIX, STANDARD.encode(bytes)))
+    }
+
+    /// decrypt a stored value. Values that were never encrypted are returned as is
+    pub fn decrypt(&self, value: &str) -> anyhow::Result<String> {
+        let encoded = match value.strip_prefix(PREFIX) {
+            Some(encoded) => encoded,
+            None => return Ok(value.to_string()),
+        };
+
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|_| anyhow!("Encrypted setting value is not valid base64"))?;
+        if bytes.len() < NONCE_LEN {
+            return Err(anyhow!("Encrypted setting value is too short"));
+        }
+
+        let (nonce, encrypted) = bytes.split_at(NONCE_LEN);
+        let decrypted = Aes256Gcm::new(&self.key)
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| anyhow!("Unable to decrypt setting value, the key does not match"))?;
+        Ok(String::from_utf8(decrypted)?)
+    }
+
+    /// decrypt a stored value with this key and encrypt it again with another.
+    /// plain text values are encrypted for the first time
+    pub fn reencrypt(&self, value: &str, to: &SecretKey) -> anyhow::Result<String> {
+        to.encrypt(&self.decrypt(value)?)
+    }
+}
+
+/// checks if a stored value has been encrypted
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// how the values being moved to a new key are currently stored
+#[derive(Clone, Copy)]
+enum StoredAs<'a> {
+    /// stored while the setting was not secret. Every value is plain text, even one that looks encrypted
+    Plain,
+    /// stored as a secret setting, encrypted with the key when there is one
+    Sealed(Option<&'a SecretKey>),
+}
+
+/// move a stored value from one key to another. Plain text values only need the new key
+fn reseal(value: &str, from: StoredAs, to: &SecretKey) -> anyhow::Result<String> {
+    match from {
+        StoredAs::Plain => to.encrypt(value),
+        StoredAs::Sealed(Some(from)) => from.reencrypt(value, to),
+        StoredAs::Sealed(None) if !is_encrypted(value) => to.encrypt(value),
+        StoredAs::Sealed(None) => Err(anyhow!(
+            "Unable to decrypt setting value, no settings key has been configured"
+        )),
+    }
+}
+
+impl<Extension> ApplicationSettings<Extension>
+where
+    Extension: Clone,
+{
+    /// encrypt secret settings with this key instead of the one in `SETTINGS_KEY`
+    pub fn with_secret_key(mut self, key: SecretKey) -> Self {
+        self.secret_key = Some(key);
+        self
+    }
+
+    /// the value as it should be stored. Secret settings are encrypted
+    pub(super) fn seal(&self, name: &str, value: &str) -> anyhow::Result<String> {
+        if !self.schemas.get(name).is_some_and(|schema| schema.is_secret()) {
+            return Ok(value.to_string());
+        }
+
+        self.secret_key
+            .as_ref()
+            .ok_or_else(|| anyhow!("Setting {} is secret but no settings key has been configured", name))?
+            .encrypt(value)
+    }
+
+    /// the readable value of a stored value. Secret settings are decrypted
+    pub(super) fn reveal(&self, name: &str, value: &str) -> anyhow::Result<String> {
+        let secret = self.schemas.get(name).is_some_and(|schema| schema.is_secret());
+        if !secret || !is_encrypted(value) {
+            return Ok(value.to_string());
+        }
+
+        self.secret_key
+            .as_ref()
+            .ok_or_else(|| anyhow!("Setting {} is secret but no settings key has been configured", name))?
+            .decrypt(value)
+            .map_err(|err| anyhow!("Unable to read secret setting {}: {}", name, err))
+    }
+
+    /// same as `reveal`, but a value that cannot be decrypted is logged and treated as not set
+    pub(super) fn readable(&self, name: &str, value: &str) -> Option<String> {
+        self.reveal(name, value).map_err(|err| tracing::error!("{}", err)).ok()
+    }
+
+    /// checks if the setting is marked as secret
+    pub fn is_secret(&self, name: &str) -> bool {
+        self.schemas.get(name).is_some_and(|schema| schema.is_secret())
+    }
+
+    /// re-encrypt every stored value of every secret setting, including the settings history, with a new key.
+    /// values are written in a single transaction and this instance switches to the new key afterwards.
+    /// returns the amount of values that were re-encrypted
+    ///
+    /// note: other instances keep using the old key until they are restarted with the new one in `SETTINGS_KEY`
+    pub async fn rotate_secret_key(&mut self, key: SecretKey) -> anyhow::Result<u64> {
+        let names = self
+            .schemas
+            .values()
+            .filter(|schema| schema.is_secret())
+            .map(|schema| schema.name().to_string())
+            .collect::<Vec<_>>();
+
+        let from = self.secret_key.clone();
+        let total = self
+            .reencrypt_values(&names, StoredAs::Sealed(from.as_ref()), &key)
+            .await?;
+        self.secret_key = Some(key);
+        Ok(total)
+    }
+
+    /// encrypt the values of a setting that were stored while it was not secret yet
+    pub(super) async fn encrypt_existing(&mut self, name: &str) -> anyhow::Result<u64> {
+        let key = self
+            .secret_key
+            .clone()
+            .ok_or_else(|| anyhow!("Setting {} is secret but no settings key has been configured", name))?;
+        self.reencrypt_values(&[name.to_string()], StoredAs::Plain, &key).await
+    }
+
+    /// move every stored and cached value of the settings from one key to another
+    async fn reencrypt_values(&mut self, names: &[String], from: StoredAs<'_>, to: &SecretKey) -> anyhow::Result<u64> {
+        let settings = names
+            .iter()
+            .filter_map(|name| self.base.get(name).map(|(model, _)| model.id))
+            .collect::<Vec<_>>();
+        if settings.is_empty() {
+            return Ok(0);
+        }
+
+        let application = self.application.record.id;
+        let txn = self.application.state.database_core.begin().await?;
+        let mut total = 0;
+
+        let models = application_global_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_global_settings::Column::Application.eq(application))
+                    .add(application_global_settings::Column::Setting.is_in(settings.clone())),
+            )
+            .all(&txn)
+            .await?;
+        for model in models.into_iter() {
+            application_global_settings::Entity::update_many()
+                .col_expr(
+                    application_global_settings::Column::Value,
+                    Expr::value(reseal(&model.value, from, to)?),
+                )
+                .filter(application_global_settings::Column::Id.eq(model.id))
+                .exec(&txn)
+                .await?;
+            total += 1;
+        }
+
+        let models = application_user_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_user_settings::Column::Application.eq(application))
+                    .add(application_user_settings::Column::Setting.is_in(settings.clone())),
+            )
+            .all(&txn)
+            .await?;
+        for model in models.into_iter() {
+            application_user_settings::Entity::update_many()
+                .col_expr(
+                    application_user_settings::Column::Value,
+                    Expr::value(reseal(&model.value, from, to)?),
+                )
+                .filter(application_user_settings::Column::Id.eq(model.id))
+                .exec(&txn)
+                .await?;
+            total += 1;
+        }
+
+        let models = application_scoped_settings::Entity::find()
+            .filter(
+                Condition::all()
+                    .add(application_scoped_settings::Column::Application.eq(application))
+                    .add(application_scoped_settings::Column::Setting.is_in(settings.clone())),
+            )
+            .all(&txn)
+            .await?;
+        for model in models.into_iter() {
+            application_scoped_settings::Entity::update_many()
+                .col_expr(
+                    application_scoped_settings::Column::Value,
+                    Expr::value(reseal(&model.value, from, to)?),
+                )
+                .filter(application_scoped_settings::Column::Id.eq(model.id))
+                .exec(&txn)
+                .await?;
+            total += 1;
+        }
+
+        total += reencrypt_history(&txn, application, &settings, from, to).await?;
+        txn.commit().await?;
+
+        // updated_at is left alone on purpose. The values did not change so nobody needs to be told about it
+        let cached = self
+            .global
+            .iter_mut()
+            .filter(|(name, _)| names.contains(name))
+            .map(|(_, (model, _))| &mut model.value)
+            .chain(
+                self.user
+                    .iter_mut()
+                    .filter(|((_, name), _)| names.contains(name))
+                    .map(|(_, (model, _))| &mut model.value),
+            )
+            .chain(
+                self.scoped
+                    .iter_mut()
+                    .filter(|((_, _, name), _)| names.contains(name))
+                    .map(|(_, (model, _))| &mut model.value),
+            );
+        for value in cached {
+            *value = reseal(value, from, to)?;
+        }
+
+        Ok(total)
+    }
+}
+
+/// re-encrypt the old and new values recorded in the settings history
+async fn reencrypt_history<C: ConnectionTrait>(
+    db: &C,
+    application: RecordId,
+    settings: &[RecordId],
+    from: StoredAs<'_>,
+    to: &SecretKey,
+) -> anyhow::Result<u64> {
+    let revisions = application_settings_history::Entity::find()
+        .filter(
+            Condition::all()
+                .add(application_settings_history::Column::Application.eq(application))
+                .add(application_settings_history::Column::Setting.is_in(settings.to_vec())),
+        )
+        .all(db)
+        .await?;
+
+    let mut total = 0;
+    for revision in revisions.into_iter() {
+        let old_value = revision.old_value.map(|v| reseal(&v, from, to)).transpose()?;
+        let new_value = revision.new_value.map(|v| reseal(&v, from, to)).transpose()?;
+        application_settings_history::Entity::update_many()
+            .col_expr(application_settings_history::Column::OldValue, Expr::value(old_value))
+            .col_expr(application_settings_history::Column::NewValue, Expr::value(new_value))
+            .filter(application_settings_history::Column::Id.eq(revision.id))
+            .exec(db)
+            .await?;
+        total += 1;
+    }
+
+    Ok(total)
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretKey").field("key", &REDACTED).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn secret_key_test() -> anyhow::Result<()> {
+        let key = SecretKey::generate();
+        let encrypted = key.encrypt("token")?;
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, key.encrypt("token")?);
+        assert_eq!(key.decrypt(&encrypted)?, "token");
+        assert_eq!(key.decrypt("plain")?, "plain");
+
+        let rotated = SecretKey::from_base64(&SecretKey::generate().to_base64())?;
+        let reencrypted = key.reencrypt(&encrypted, &rotated)?;
+        assert_eq!(rotated.decrypt(&reencrypted)?, "token");
+        assert!(key.decrypt(&reencrypted).is_err());
+
+        // values stored before the setting was secret are never mistaken for encrypted ones
+        let sealed = reseal("enc:plain", StoredAs::Plain, &key)?;
+        assert_eq!(key.decrypt(&sealed)?, "enc:plain");
+        assert_eq!(
+            rotated.decrypt(&reseal(&sealed, StoredAs::Sealed(Some(&key)), &rotated)?)?,
+            "enc:plain"
+        );
+
+        assert!(!format!("{:?}", key).contains(&key.to_base64()));
+        assert!(SecretKey::from_base64("c2hvcnQ=").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveValue, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    TransactionTrait,
+};
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use super::events::{SettingEvent, SettingsTransport};
+use super::ApplicationSettingType;
+use crate::{
+    alias::RecordId,
+    entities::{
+        application_global_settings, application_scoped_settings, application_settings_history,
+        application_user_settings,
+    },
+    util::unix_timestamp,
+};
+
+/// a change to a single setting value that still has to be persisted. A new value of None removes the value
+pub(super) struct SettingWrite {
+    pub application: RecordId,
+    pub setting: RecordId,
+    pub setting_type: ApplicationSettingType,
+    /// the user, guild or channel id. Empty for global settings
+    pub id: String,
+    pub actor: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// a write waiting in the queue along with the event to publish once it is stored
+struct QueuedWrite {
+    write: SettingWrite,
+    event: SettingEvent,
+    transport: Arc<dyn SettingsTransport>,
+    done: oneshot::Sender<anyhow::Result<()>>,
+}
+
+/// persists writes one at a time in the order they were queued, so the database and the settings history
+/// end up in the same order the changes were made in. Clones share the queue.
+/// the background task stops once every clone has been dropped
+#[derive(Clone)]
+pub(super) struct WriteQueue {
+    sender: mpsc::UnboundedSender<QueuedWrite>,
+}
+
+impl WriteQueue {
+    pub fn start(db: DatabaseConnection) -> WriteQueue {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<QueuedWrite>();
+        tokio::spawn(async move {
+            while let Some(queued) = receiver.recv().await {
+                let result = queued.persist(&db).await;
+                let _ = queued.done.send(result);
+            }
+        });
+        WriteQueue { sender }
+    }
+
+    /// queue the write. The receiver resolves once it has been persisted and published
+    pub fn push(
+        &self,
+        write: SettingWrite,
+        event: SettingEvent,
+        transport: Arc<dyn SettingsTransport>,
+    ) -> oneshot::Receiver<anyhow::Result<()>> {
+        let (done, receiver) = oneshot::channel();
+        let queued = QueuedWrite {
+            write,
+            event,
+            transport,
+            done,
+        };
+        if let Err(err) = self.sender.send(queued) {
+            let _ = err.0.done.send(Err(anyhow!("Settings write queue has stopped")));
+        }
+        receiver
+    }
+
+    /// queue the write and wait until it has been persisted and published
+    pub async fn write(
+        &self,
+        write: SettingWrite,
+        event: SettingEvent,
+        transport: Arc<dyn SettingsTransport>,
+    ) -> anyhow::Result<()> {
+        self.push(write, event, transport)
+            .await
+            .map_err(|_| anyhow!("Settings write queue has stopped"))?
+    }
+}
+
+impl QueuedWrite {
+    async fn persist(&self, db: &DatabaseConnection) -> anyhow::Result<()> {
+        let setting_type = self.write.setting_type.as_str();
+        if let Err(err) = self.write.persist(db).await {
+            tracing::error!("Unable to persist {} setting: {}", setting_type, err);
+            return Err(err);
+        }
+
+        // other instances should only hear about values that made it into the database
+        let result = self.transport.publish(&self.event).await;
+        if let Err(err) = result.as_ref() {
+            tracing::error!("Unable to publish {} setting: {}", setting_type, err);
+        }
+        result
+    }
+}
+
+impl SettingWrite {
+    /// write the value and its history entry in a single transaction
+    pub async fn persist(&self, db: &DatabaseConnection) -> anyhow::Result<()> {
+        let txn = db.begin().await?;
+
+        if self.old_value != self.new_value {
+            self.record_history(&txn).await?;
+        }
+
+        match self.new_value.as_deref() {
+            Some(value) => self.upsert(&txn, value).await?,
+            None => self.soft_delete(&txn).await?,
+        }
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    fn hash(&self) -> String {
+        let seed = format!(
+            "{}|{}|{}|{}|{}|{}",
+            unix_timestamp(),
+            self.application,
+            self.setting_type.as_str(),
+            self.setting,
+            self.id,
+            Uuid::new_v4()
+        );
+        format!("{:x}", md5::compute(seed))
+    }
+
+    /// record the change in the settings history. A value of None means the setting was not set
+    async fn record_history<C: ConnectionTrait>(&self, db: &C) -> anyhow::Result<()> {
+        let active = application_settings_history::ActiveModel {
+            id: ActiveValue::NotSet,
+            application: ActiveValue::Set(self.application),
+            setting: ActiveValue::Set(self.setting),
+            hash_user: ActiveValue::Set(self.id.clone()),
+            scope: ActiveValue::Set(self.setting_type.as_str().to_string()),
+            old_value: ActiveValue::Set(self.old_value.clone()),
+            new_value: ActiveValue::Set(self.new_value.clone()),
+            actor: ActiveValue::Set(self.actor.clone()),
+            created_at: ActiveValue::Set(unix_timestamp()),
+            updated_at: ActiveValue::Set(0),
+            deleted_at: ActiveValue::Set(0),
+        };
+
+        application_settings_history::Entity::insert(active)
+            .exec_without_returning(db)
+            .await?;
+        Ok(())
+    }
+
+    /// insert the value, or update it in place when the unique index already holds a row (including a soft deleted one)
+    async fn upsert<C: ConnectionTrait>(&self, db: &C, value: &str) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        match self.setting_type {
+            ApplicationSettingType::Global => {
+                let active = application_global_settings::ActiveModel {
+                    id: ActiveValue::NotSet,
+                    application: ActiveValue::Set(self.application),
+                    hash: ActiveValue::Set(self.hash()),
+                    setting: ActiveValue::Set(self.setting),
+                    value: ActiveValue::Set(value.to_string()),
+                    created_at: ActiveValue::Set(timestamp),
+                    updated_at: ActiveValue::Set(timestamp),
+                    deleted_at: ActiveValue::Set(0),
+                };
+
+                application_global_settings::Entity::insert(active)
+                    .on_conflict(
+                        OnConflict::columns([
+                            application_global_settings::Column::Application,
+                            application_global_settings::Column::Setting,
+                        ])
+                        .update_columns([
+                            application_global_settings::Column::Value,
+                            application_global_settings::Column::UpdatedAt,
+                            application_global_settings::Column::DeletedAt,
+                        ])
+                        .to_owned(),
+                    )
+                    .exec_without_returning(db)
+                    .await?;
+            }
+            ApplicationSettingType::User => {
+                let active = application_user_settings::ActiveModel {
+                    id: ActiveValue::NotSet,
+                    application: ActiveValue::Set(self.application),
+                    hash: ActiveValue::Set(self.hash()),
+                    hash_user: ActiveValue::Set(self.id.clone()),
+                    setting: ActiveValue::Set(self.setting),
+                    value: ActiveValue::Set(value.to_string()),
+                    created_at: ActiveValue::Set(timestamp),
+                    updated_at: ActiveValue::Set(timestamp),
+                    deleted_at: ActiveValue::Set(0),
+                };
+
+                application_user_settings::Entity::insert(active)
+                    .on_conflict(
+                        OnConflict::columns([
+                            application_user_settings::Column::Application,
+                            application_user_settings::Column::Setting,
+                            application_user_settings::Column::HashUser,
+                        ])
+                        .update_columns([
+                            application_user_settings::Column::Value,
+                            application_user_settings::Column::UpdatedAt,
+                            application_user_settings::Column::DeletedAt,
+                        ])
+                        .to_owned(),
+                    )
+                    .exec_without_returning(db)
+                    .await?;
+            }
+            _ => {
+                let active = application_scoped_settings::ActiveModel {
+                    id: ActiveValue::NotSet,
+                    application: ActiveValue::Set(self.application),
+                    hash: ActiveValue::Set(self.hash()),
+                    scope_type: ActiveValue::Set(self.setting_type.as_str().to_string()),
+                    scope_id: ActiveValue::Set(self.id.clone()),
+                    setting: ActiveValue::Set(self.setting),
+                    value: ActiveValue::Set(value.to_string()),
+                    created_at: ActiveValue::Set(timestamp),
+                    updated_at: ActiveValue::Set(timestamp),
+                    deleted_at: ActiveValue::Set(0),
+                };
+
+                application_scoped_settings::Entity::insert(active)
+                    .on_conflict(
+                        OnConflict::columns([
+                            application_scoped_settings::Column::Application,
+                            application_scoped_settings::Column::Setting,
+                            application_scoped_settings::Column::ScopeType,
+                            application_scoped_settings::Column::ScopeId,
+                        ])
+                        .update_columns([
+                            application_scoped_settings::Column::Value,
+                            application_scoped_settings::Column::UpdatedAt,
+                            application_scoped_settings::Column::DeletedAt,
+                        ])
+                        .to_owned(),
+                    )
+                    .exec_without_returning(db)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn soft_delete<C: ConnectionTrait>(&self, db: &C) -> anyhow::Result<()> {
+        let timestamp = unix_timestamp();
+        match self.setting_type {
+            ApplicationSettingType::Global => {
+                application_global_settings::Entity::update_many()
+                    .col_expr(application_global_settings::Column::DeletedAt, Expr::value(timestamp))
+                    .col_expr(application_global_settings::Column::UpdatedAt, Expr::value(timestamp))
+                    .filter(
+                        Condition::all()
+                            .add(application_global_settings::Column::Application.eq(self.application))
+                            .add(application_global_settings::Column::Setting.eq(self.setting))
+                            .add(application_global_settings::Column::DeletedAt.eq(0)),
+                    )
+                    .exec(db)
+                    .await?;
+            }
+            ApplicationSettingType::User => {
+                application_user_settings::Entity::update_many()
+                    .col_expr(application_user_settings::Column::DeletedAt, Expr::value(timestamp))
+                    .col_expr(application_user_settings::Column::UpdatedAt, Expr::value(timestamp))
+                    .filter(
+                        Condition::all()
+                            .add(application_user_settings::Column::Application.eq(self.application))
+                            .add(application_user_settings::Column::Setting.eq(self.setting))
+                            .add(application_user_settings::Column::HashUser.eq(self.id.as_str()))
+                            .add(application_user_settings::Column::DeletedAt.eq(0)),
+                    )
+                    .exec(db)
+                    .await?;
+            }
+            _ => {
+                application_scoped_settings::Entity::update_many()
+                    .col_expr(application_scoped_settings::Column::DeletedAt, Expr::value(timestamp))
+                    .col_expr(application_scoped_settings::Column::UpdatedAt, Expr::value(timestamp))
+                    .filter(
+                        Condition::all()
+                            .add(application_scoped_settings::Column::Application.eq(self.application))
+                            .add(application_scoped_settings::Column::Setting.eq(self.setting))
+                            .add(application_scoped_settings::Column::ScopeType.eq(self.setting_type.as_str()))
+                            .add(application_scoped_settings::Column::ScopeId.eq(self.id.as_str()))
+                            .add(application_scoped_settings::Column::DeletedAt.eq(0)),
+                    )
+                    .exec(db)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
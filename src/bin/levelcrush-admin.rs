@@ -0,0 +1,766 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::TimeZone;
+use clap::{Parser, Subcommand, ValueEnum};
+use levelcrush::app::keys::DEFAULT_ROTATION_GRACE;
+use levelcrush::app::process::query::{LogQuery, ProcessLog};
+use levelcrush::app::process::retention::PurgeConfig;
+use levelcrush::app::process::LogLevel;
+use levelcrush::app::settings::export::{ExportFormat, ImportMode, SettingsExport};
+use levelcrush::app::settings::schema::{SettingSchema, SettingValueType};
+use levelcrush::app::settings::secret::{SecretKey, REDACTED};
+use levelcrush::app::settings::ApplicationSettings;
+use levelcrush::app::{Application, ApplicationState};
+use levelcrush::env::{self, EnvVar};
+use levelcrush::{anyhow, database};
+use tracing_subscriber::EnvFilter;
+
+/// Manage LevelCrush applications, processes, settings and logs directly against DATABASE_URL_CORE
+#[derive(Parser)]
+#[command(name = "levelcrush-admin", version)]
+struct Cli {
+    /// how results are printed
+    #[arg(long, short, value_enum, global = true, default_value_t = OutputMode::Table)]
+    output: OutputMode,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputMode {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// manage registered applications
+    #[command(subcommand)]
+    App(AppCommand),
+
+    /// inspect the processes of an application
+    #[command(subcommand)]
+    Process(ProcessCommand),
+
+    /// manage global and user settings of an application
+    #[command(subcommand)]
+    Settings(SettingsCommand),
+
+    /// read the process logs of an application
+    #[command(subcommand)]
+    Logs(LogsCommand),
+}
+
+#[derive(Subcommand)]
+enum AppCommand {
+    /// register a new application
+    Register { name: String, host: String },
+
+    /// list registered applications
+    List {
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+        #[arg(long, default_value_t = 25)]
+        limit: u32,
+        /// include soft deleted applications
+        #[arg(long)]
+        deleted: bool,
+    },
+
+    /// rotate a named key. The old key stays valid for the grace period
+    Rotate {
+        /// application id (hash)
+        app: String,
+        #[arg(long, default_value = "default")]
+        key: String,
+        /// grace period in seconds
+        #[arg(long, default_value_t = DEFAULT_ROTATION_GRACE.as_secs())]
+        grace: u64,
+    },
+
+    /// soft delete an application along with its processes, logs, keys and settings
+    Delete { app: String },
+
+    /// restore a soft deleted application
+    Restore { app: String },
+}
+
+#[derive(Subcommand)]
+enum ProcessCommand {
+    /// list the processes of an application
+    List { app: String },
+}
+
+#[derive(Subcommand)]
+enum SettingsCommand {
+    /// get a setting. With --user the user value is returned, falling back to the global value.
+    /// secret values are redacted unless --reveal is passed
+    Get {
+        /// application id (hash)
+        app: String,
+        name: String,
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long)]
+        reveal: bool,
+    },
+
+    /// set a setting. With --user the value is only set for that user
+    Set {
+        /// application id (hash)
+        app: String,
+        name: String,
+        value: String,
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// list global settings or with --user the settings of that user
+    List {
+        /// application id (hash)
+        app: String,
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// delete a global setting or with --user the setting of that user
+    Delete {
+        /// application id (hash)
+        app: String,
+        name: String,
+        #[arg(long)]
+        user: Option<String>,
+    },
+
+    /// show the changes made to a global setting or with --user the setting of that user
+    History {
+        /// application id (hash)
+        app: String,
+        name: String,
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: u64,
+    },
+
+    /// put a setting back to the value it had before the revision
+    Rollback {
+        /// application id (hash)
+        app: String,
+        revision: i64,
+    },
+
+    /// print every setting definition and global value. With --users the user, guild and channel values are included
+    Export {
+        /// application id (hash)
+        app: String,
+        #[arg(long)]
+        users: bool,
+        #[arg(long, value_enum, default_value_t = FileFormat::Yaml)]
+        format: FileFormat,
+    },
+
+    /// apply a yaml or json export to the application. The format is picked from the file extension
+    Import {
+        /// application id (hash)
+        app: String,
+        file: String,
+        #[arg(long, value_enum, default_value_t = ImportStrategy::Merge)]
+        mode: ImportStrategy,
+        /// only show what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// mark a setting as secret. Values stored for it are encrypted with SETTINGS_KEY
+    Secret {
+        /// application id (hash)
+        app: String,
+        name: String,
+    },
+
+    /// re-encrypt every secret value with the key in SETTINGS_KEY_NEXT, or a newly generated key when it is not set.
+    /// the new key is printed and has to replace SETTINGS_KEY afterwards
+    RotateKey {
+        /// application id (hash)
+        app: String,
+    },
+}
+
+/// environment variable rotate-key reads the new settings key from
+const SETTINGS_KEY_NEXT: &str = "SETTINGS_KEY_NEXT";
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FileFormat {
+    Yaml,
+    Json,
+}
+
+impl From<FileFormat> for ExportFormat {
+    fn from(format: FileFormat) -> ExportFormat {
+        match format {
+            FileFormat::Yaml => ExportFormat::Yaml,
+            FileFormat::Json => ExportFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ImportStrategy {
+    Merge,
+    Replace,
+    Skip,
+}
+
+impl From<ImportStrategy> for ImportMode {
+    fn from(strategy: ImportStrategy) -> ImportMode {
+        match strategy {
+            ImportStrategy::Merge => ImportMode::Merge,
+            ImportStrategy::Replace => ImportMode::Replace,
+            ImportStrategy::Skip => ImportMode::Skip,
+        }
+    }
+}
+
+/// recorded as the actor of any setting changed through this tool
+const SETTINGS_ACTOR: &str = "levelcrush-admin";
+
+#[derive(Subcommand)]
+enum LogsCommand {
+    /// show the most recent logs
+    Tail {
+        /// application id (hash)
+        app: String,
+        #[arg(long)]
+        process: Option<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: u64,
+        /// keep polling for new logs
+        #[arg(long, short)]
+        follow: bool,
+        /// seconds between polls when following
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+
+    /// search log content
+    Search {
+        /// application id (hash)
+        app: String,
+        term: String,
+        #[arg(long)]
+        process: Option<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: u64,
+    },
+
+    /// delete logs that are past their retention
+    Purge {
+        /// application id (hash)
+        app: String,
+        /// directory expired logs are archived to, for retention rules that archive
+        #[arg(long)]
+        archive: Option<PathBuf>,
+        #[arg(long, default_value_t = 1000)]
+        batch: u64,
+    },
+}
+
+/// rows that are printed as either a table or a json array of objects
+struct Output {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Output {
+    fn new(headers: Vec<&'static str>) -> Output {
+        Output {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn print(&self, mode: OutputMode) {
+        match mode {
+            OutputMode::Json => {
+                let values = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        self.headers
+                            .iter()
+                            .zip(row.iter())
+                            .map(|(header, value)| (header.to_string(), serde_json::Value::String(value.clone())))
+                            .collect::<serde_json::Map<_, _>>()
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&values).unwrap_or_default());
+            }
+            OutputMode::Table => {
+                let mut widths = self.headers.iter().map(|h| h.len()).collect::<Vec<_>>();
+                for row in self.rows.iter() {
+                    for (i, value) in row.iter().enumerate() {
+                        widths[i] = widths[i].max(value.chars().count());
+                    }
+                }
+
+                let line = |values: Vec<&str>| {
+                    values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| format!("{:width$}", value, width = widths[i]))
+                        .collect::<Vec<_>>()
+                        .join("  ")
+                };
+
+                println!("{}", line(self.headers.clone()));
+                let dashes = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+                println!("{}", line(dashes.iter().map(String::as_str).collect()));
+                for row in self.rows.iter() {
+                    println!("{}", line(row.iter().map(|v| v.as_str()).collect()));
+                }
+            }
+        }
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    if timestamp == 0 {
+        return String::new();
+    }
+
+    chrono::Utc
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map_or(timestamp.to_string(), |dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn level_name(level: i8) -> &'static str {
+    LogLevel::try_from(level).map_or("unknown", |level| level.as_str())
+}
+
+async fn application(app: &str, state: &ApplicationState<()>) -> anyhow::Result<Application<()>> {
+    Application::find(app, state)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No application found matching {}", app))
+}
+
+async fn active_application(app: &str, state: &ApplicationState<()>) -> anyhow::Result<Application<()>> {
+    let application = application(app, state).await?;
+    if application.deleted_at() > 0 {
+        Err(anyhow::anyhow!("Application {} has been deleted", app))
+    } else {
+        Ok(application)
+    }
+}
+
+async fn run_app(command: AppCommand, state: &ApplicationState<()>, mode: OutputMode) -> anyhow::Result<()> {
+    match command {
+        AppCommand::Register { name, host } => {
+            let app = Application::register(&name, &host, state).await?;
+            let mut output = Output::new(vec!["id", "name", "host", "secret"]);
+            output.row(vec![
+                app.hash().to_string(),
+                app.name().to_string(),
+                app.host().to_string(),
+                app.secret().to_string(),
+            ]);
+            output.print(mode);
+        }
+        AppCommand::List { page, limit, deleted } => {
+            let (apps, pagination) = Application::list(page, limit, deleted, state).await?;
+            let mut output = Output::new(vec!["id", "name", "host", "deleted_at"]);
+            for app in apps.iter() {
+                output.row(vec![
+                    app.hash().to_string(),
+                    app.name().to_string(),
+                    app.host().to_string(),
+                    format_timestamp(app.deleted_at()),
+                ]);
+            }
+            output.print(mode);
+
+            if mode == OutputMode::Table {
+                println!(
+                    "\nPage {} of {} ({} total)",
+                    pagination.page, pagination.total_pages, pagination.total_results
+                );
+            }
+        }
+        AppCommand::Rotate { app, key, grace } => {
+            let app = active_application(&app, state).await?;
+            let key = app.rotate_key(&key, Duration::from_secs(grace)).await?;
+            let mut output = Output::new(vec!["id", "key", "secret", "scopes"]);
+            output.row(vec![
+                app.hash().to_string(),
+                key.name().to_string(),
+                key.secret().to_string(),
+                key.scopes().join(","),
+            ]);
+            output.print(mode);
+        }
+        AppCommand::Delete { app } => {
+            let mut app = application(&app, state).await?;
+            app.delete().await?;
+            let mut output = Output::new(vec!["id", "deleted_at"]);
+            output.row(vec![app.hash().to_string(), format_timestamp(app.deleted_at())]);
+            output.print(mode);
+        }
+        AppCommand::Restore { app } => {
+            let mut app = application(&app, state).await?;
+            app.restore().await?;
+            let mut output = Output::new(vec!["id", "name"]);
+            output.row(vec![app.hash().to_string(), app.name().to_string()]);
+            output.print(mode);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_process(command: ProcessCommand, state: &ApplicationState<()>, mode: OutputMode) -> anyhow::Result<()> {
+    match command {
+        ProcessCommand::List { app } => {
+            let app = active_application(&app, state).await?;
+            let mut output = Output::new(vec!["id", "hash", "name", "created_at"]);
+            for process in app.processes().await?.iter() {
+                output.row(vec![
+                    process.id().to_string(),
+                    process.hash().to_string(),
+                    process.name().to_string(),
+                    format_timestamp(process.created_at()),
+                ]);
+            }
+            output.print(mode);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_settings(command: SettingsCommand, state: &ApplicationState<()>, mode: OutputMode) -> anyhow::Result<()> {
+    match command {
+        SettingsCommand::Get {
+            app,
+            name,
+            user,
+            reveal,
+        } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            let value = match user.as_ref() {
+                Some(user) => settings.get_user_or_load(user, &name).await?,
+                None => settings.get_global(&name),
+            }
+            .unwrap_or_default();
+            let value = match reveal {
+                true => value,
+                false => redact(&settings, &name, value),
+            };
+
+            let mut output = Output::new(vec!["name", "user", "value"]);
+            output.row(vec![name, user.unwrap_or_default(), value]);
+            output.print(mode);
+        }
+        SettingsCommand::Set { app, name, value, user } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            let handle = match user.as_ref() {
+                Some(user) => settings.set_user(user, &name, &value).await?,
+                None => settings.set_global(&name, &value).await?,
+            };
+            handle.await??;
+
+            let value = redact(&settings, &name, value);
+            let mut output = Output::new(vec!["name", "user", "value"]);
+            output.row(vec![name, user.unwrap_or_default(), value]);
+            output.print(mode);
+        }
+        SettingsCommand::List { app, user } => {
+            let app = active_application(&app, state).await?;
+            let settings = ApplicationSettings::load(&app).await?;
+            let values = match user.as_ref() {
+                Some(user) => settings.list_user(user).await?,
+                None => settings.list_global(),
+            };
+
+            let mut output = Output::new(vec!["name", "user", "value"]);
+            for (name, value) in values.into_iter() {
+                let value = redact(&settings, &name, value);
+                output.row(vec![name, user.clone().unwrap_or_default(), value]);
+            }
+            output.print(mode);
+        }
+        SettingsCommand::Delete { app, name, user } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            match user.as_ref() {
+                Some(user) => settings.delete_user(user, &name).await?,
+                None => settings.delete_global(&name).await?,
+            };
+
+            let mut output = Output::new(vec!["name", "user", "deleted"]);
+            output.row(vec![name, user.unwrap_or_default(), "true".to_string()]);
+            output.print(mode);
+        }
+        SettingsCommand::History { app, name, user, limit } => {
+            let app = active_application(&app, state).await?;
+            let settings = ApplicationSettings::load(&app).await?;
+            let revisions = settings.history(&name, user.as_deref(), limit).await?;
+
+            let mut output = Output::new(vec!["revision", "changed_at", "actor", "old", "new"]);
+            for revision in revisions.into_iter() {
+                output.row(vec![
+                    revision.id.to_string(),
+                    format_timestamp(revision.created_at),
+                    revision.actor,
+                    redact(&settings, &name, revision.old_value.unwrap_or_default()),
+                    redact(&settings, &name, revision.new_value.unwrap_or_default()),
+                ]);
+            }
+            output.print(mode);
+        }
+        SettingsCommand::Rollback { app, revision } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            settings.rollback(revision).await?;
+
+            let mut output = Output::new(vec!["revision", "rolled_back"]);
+            output.row(vec![revision.to_string(), "true".to_string()]);
+            output.print(mode);
+        }
+        SettingsCommand::Export { app, users, format } => {
+            let app = active_application(&app, state).await?;
+            let settings = ApplicationSettings::load(&app).await?;
+            let export = settings.export(users).await?;
+            print!("{}", export.to_string(format.into())?);
+        }
+        SettingsCommand::Import {
+            app,
+            file,
+            mode: strategy,
+            dry_run,
+        } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            let input = tokio::fs::read_to_string(&file).await?;
+            let export = SettingsExport::parse(&input, ExportFormat::from_path(&file))?;
+            let changes = settings.import(&export, strategy.into(), dry_run).await?;
+
+            let mut output = Output::new(vec!["name", "scope", "id", "old", "new"]);
+            for change in changes.iter() {
+                let (old_value, new_value) = change.display_values();
+                output.row(vec![
+                    change.name.clone(),
+                    change.setting_type.as_str().to_string(),
+                    change.user.clone().unwrap_or_default(),
+                    old_value.unwrap_or_default().to_string(),
+                    new_value.unwrap_or_default().to_string(),
+                ]);
+            }
+            output.print(mode);
+        }
+        SettingsCommand::Secret { app, name } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            let schema = settings
+                .schema(&name)
+                .cloned()
+                .unwrap_or_else(|| SettingSchema::new(&name, SettingValueType::String));
+            settings.register(schema.with_secret()).await?;
+
+            let mut output = Output::new(vec!["name", "secret"]);
+            output.row(vec![name, "true".to_string()]);
+            output.print(mode);
+        }
+        SettingsCommand::RotateKey { app } => {
+            let app = active_application(&app, state).await?;
+            let mut settings = ApplicationSettings::load(&app).await?.with_actor(SETTINGS_ACTOR);
+            let key = SecretKey::from_env(EnvVar::Custom(SETTINGS_KEY_NEXT))?.unwrap_or_else(SecretKey::generate);
+            let total = settings.rotate_secret_key(key.clone()).await?;
+
+            let mut output = Output::new(vec!["reencrypted", "key"]);
+            output.row(vec![total.to_string(), key.to_base64()]);
+            output.print(mode);
+        }
+    }
+
+    Ok(())
+}
+
+/// hide the value of secret settings
+fn redact(settings: &ApplicationSettings<()>, name: &str, value: String) -> String {
+    match settings.is_secret(name) && !value.is_empty() {
+        true => REDACTED.to_string(),
+        false => value,
+    }
+}
+
+/// a query over the logs of the application, newest first
+async fn log_query(
+    app: &Application<()>,
+    process: Option<&str>,
+    term: Option<&str>,
+    after: i64,
+    limit: u64,
+) -> anyhow::Result<LogQuery> {
+    let mut query = app.logs().with_after(after).with_limit(limit);
+
+    if let Some(process) = process {
+        // app.process(...) would create the process if it did not exist
+        let process = app
+            .processes()
+            .await?
+            .into_iter()
+            .find(|p| p.name() == process)
+            .ok_or_else(|| anyhow::anyhow!("No process found matching {}", process))?;
+        query = query.with_process(process.id());
+    }
+
+    if let Some(term) = term {
+        query = query.with_search(term);
+    }
+
+    Ok(query)
+}
+
+/// the first page of logs of the application, newest first
+async fn query_logs(
+    app: &Application<()>,
+    process: Option<&str>,
+    term: Option<&str>,
+    limit: u64,
+) -> anyhow::Result<Vec<ProcessLog>> {
+    let query = log_query(app, process, term, 0, limit).await?;
+    Ok(query.fetch(&app.state.database_core).await?.data)
+}
+
+/// every log newer than `after`, newest first. Pages through the cursor so a burst bigger than a page is not skipped
+async fn logs_after(
+    app: &Application<()>,
+    process: Option<&str>,
+    after: i64,
+    limit: u64,
+) -> anyhow::Result<Vec<ProcessLog>> {
+    let query = log_query(app, process, None, after, limit).await?;
+    let mut page = query.fetch(&app.state.database_core).await?;
+    let mut logs = Vec::new();
+    loop {
+        logs.append(&mut page.data);
+        match page.cursor {
+            Some(cursor) => {
+                page = query
+                    .clone()
+                    .with_cursor(cursor)
+                    .fetch(&app.state.database_core)
+                    .await?
+            }
+            None => break,
+        }
+    }
+    Ok(logs)
+}
+
+fn print_logs(logs: &[ProcessLog], mode: OutputMode) {
+    let mut output = Output::new(vec!["id", "created_at", "process", "level", "content"]);
+    for log in logs.iter().rev() {
+        output.row(vec![
+            log.id.to_string(),
+            format_timestamp(log.created_at),
+            log.process.to_string(),
+            level_name(log.level).to_string(),
+            log.content.clone(),
+        ]);
+    }
+    output.print(mode);
+}
+
+async fn run_logs(command: LogsCommand, state: &ApplicationState<()>, mode: OutputMode) -> anyhow::Result<()> {
+    match command {
+        LogsCommand::Tail {
+            app,
+            process,
+            limit,
+            follow,
+            interval,
+        } => {
+            let app = active_application(&app, state).await?;
+            let logs = query_logs(&app, process.as_deref(), None, limit).await?;
+            print_logs(&logs, mode);
+
+            if follow {
+                let mut last_id = logs.first().map_or(0, |log| log.id);
+                loop {
+                    tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+                    let logs = logs_after(&app, process.as_deref(), last_id, limit).await?;
+                    if !logs.is_empty() {
+                        last_id = logs.first().map_or(last_id, |log| log.id);
+                        print_logs(&logs, mode);
+                    }
+                }
+            }
+        }
+        LogsCommand::Search {
+            app,
+            term,
+            process,
+            limit,
+        } => {
+            let app = active_application(&app, state).await?;
+            let logs = query_logs(&app, process.as_deref(), Some(&term), limit).await?;
+            print_logs(&logs, mode);
+        }
+        LogsCommand::Purge { app, archive, batch } => {
+            let app = active_application(&app, state).await?;
+            let report = app
+                .purge_logs(&PurgeConfig {
+                    batch_size: batch,
+                    archive_dir: archive,
+                })
+                .await?;
+
+            let mut output = Output::new(vec!["deleted", "archived", "files"]);
+            output.row(vec![
+                report.deleted.to_string(),
+                report.archived.to_string(),
+                report.files.len().to_string(),
+            ]);
+            output.print(mode);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+
+    // keep tracing off stdout so json output stays parseable
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let cli = Cli::parse();
+
+    if !env::exists(EnvVar::DatabaseUrlCore) {
+        return Err(anyhow::anyhow!("{} must be set", EnvVar::DatabaseUrlCore));
+    }
+
+    let db = database::connect(env::get(EnvVar::DatabaseUrlCore), 1).await;
+    let state = ApplicationState::new(db.clone(), db, ());
+
+    match cli.command {
+        Command::App(command) => run_app(command, &state, cli.output).await,
+        Command::Process(command) => run_process(command, &state, cli.output).await,
+        Command::Settings(command) => run_settings(command, &state, cli.output).await,
+        Command::Logs(command) => run_logs(command, &state, cli.output).await,
+    }
+}
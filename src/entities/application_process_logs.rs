@@ -20,6 +20,11 @@ pub struct Model {
     pub hash_sub: String,
     pub r#type: i8,
     pub content: String,
+    pub fields: Option<String>,
+    pub sub_id: String,
+    pub correlation_id: String,
+    pub module: String,
+    pub line: i32,
     pub created_at: i64,
     pub updated_at: i64,
     pub deleted_at: i64,
@@ -34,6 +39,11 @@ pub enum Column {
     HashSub,
     Type,
     Content,
+    Fields,
+    SubId,
+    CorrelationId,
+    Module,
+    Line,
     CreatedAt,
     UpdatedAt,
     DeletedAt,
@@ -68,6 +78,11 @@ impl ColumnTrait for Column {
             Self::HashSub => ColumnType::Char(Some(32u32)).def(),
             Self::Type => ColumnType::TinyInteger.def(),
             Self::Content => ColumnType::Text.def(),
+            Self::Fields => ColumnType::Text.def().null(),
+            Self::SubId => ColumnType::String(Some(255u32)).def(),
+            Self::CorrelationId => ColumnType::String(Some(64u32)).def(),
+            Self::Module => ColumnType::String(Some(255u32)).def(),
+            Self::Line => ColumnType::Integer.def(),
             Self::CreatedAt => ColumnType::BigInteger.def(),
             Self::UpdatedAt => ColumnType::BigInteger.def(),
             Self::DeletedAt => ColumnType::BigInteger.def(),
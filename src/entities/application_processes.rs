@@ -48,6 +48,7 @@ impl PrimaryKeyTrait for PrimaryKey {
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
     ApplicationProcessLogs,
+    ApplicationProcessRuns,
     Applications,
 }
 
@@ -70,6 +71,7 @@ impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
             Self::ApplicationProcessLogs => Entity::has_many(super::application_process_logs::Entity).into(),
+            Self::ApplicationProcessRuns => Entity::has_many(super::application_process_runs::Entity).into(),
             Self::Applications => Entity::belongs_to(super::applications::Entity)
                 .from(Column::Application)
                 .to(super::applications::Column::Id)
@@ -84,6 +86,12 @@ impl Related<super::application_process_logs::Entity> for Entity {
     }
 }
 
+impl Related<super::application_process_runs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationProcessRuns.def()
+    }
+}
+
 impl Related<super::applications::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Applications.def()
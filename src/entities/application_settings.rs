@@ -17,6 +17,11 @@ pub struct Model {
     pub application: i64,
     pub hash: String,
     pub name: String,
+    pub value_type: String,
+    pub value_options: Option<String>,
+    pub default_value: Option<String>,
+    pub description: Option<String>,
+    pub secret: bool,
     pub created_at: i64,
     pub updated_at: i64,
     pub deleted_at: i64,
@@ -28,6 +33,11 @@ pub enum Column {
     Application,
     Hash,
     Name,
+    ValueType,
+    ValueOptions,
+    DefaultValue,
+    Description,
+    Secret,
     CreatedAt,
     UpdatedAt,
     DeletedAt,
@@ -48,6 +58,8 @@ impl PrimaryKeyTrait for PrimaryKey {
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
     ApplicationGlobalSettings,
+    ApplicationScopedSettings,
+    ApplicationSettingsHistory,
     ApplicationUserSettings,
     Applications,
 }
@@ -60,6 +72,11 @@ impl ColumnTrait for Column {
             Self::Application => ColumnType::BigInteger.def(),
             Self::Hash => ColumnType::Char(Some(32u32)).def().unique(),
             Self::Name => ColumnType::String(Some(255u32)).def(),
+            Self::ValueType => ColumnType::String(Some(16u32)).def(),
+            Self::ValueOptions => ColumnType::Text.def().null(),
+            Self::DefaultValue => ColumnType::Text.def().null(),
+            Self::Description => ColumnType::Text.def().null(),
+            Self::Secret => ColumnType::Boolean.def(),
             Self::CreatedAt => ColumnType::BigInteger.def(),
             Self::UpdatedAt => ColumnType::BigInteger.def(),
             Self::DeletedAt => ColumnType::BigInteger.def(),
@@ -71,6 +88,8 @@ impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
             Self::ApplicationGlobalSettings => Entity::has_many(super::application_global_settings::Entity).into(),
+            Self::ApplicationScopedSettings => Entity::has_many(super::application_scoped_settings::Entity).into(),
+            Self::ApplicationSettingsHistory => Entity::has_many(super::application_settings_history::Entity).into(),
             Self::ApplicationUserSettings => Entity::has_many(super::application_user_settings::Entity).into(),
             Self::Applications => Entity::belongs_to(super::applications::Entity)
                 .from(Column::Application)
@@ -86,6 +105,18 @@ impl Related<super::application_global_settings::Entity> for Entity {
     }
 }
 
+impl Related<super::application_scoped_settings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationScopedSettings.def()
+    }
+}
+
+impl Related<super::application_settings_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationSettingsHistory.def()
+    }
+}
+
 impl Related<super::application_user_settings::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ApplicationUserSettings.def()
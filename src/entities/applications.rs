@@ -50,9 +50,14 @@ impl PrimaryKeyTrait for PrimaryKey {
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
     ApplicationGlobalSettings,
+    ApplicationKeys,
+    ApplicationLogRetention,
     ApplicationProcessLogs,
+    ApplicationProcessRuns,
     ApplicationProcesses,
+    ApplicationScopedSettings,
     ApplicationSettings,
+    ApplicationSettingsHistory,
     ApplicationUserSettings,
 }
 
@@ -76,9 +81,14 @@ impl RelationTrait for Relation {
     fn def(&self) -> RelationDef {
         match self {
             Self::ApplicationGlobalSettings => Entity::has_many(super::application_global_settings::Entity).into(),
+            Self::ApplicationKeys => Entity::has_many(super::application_keys::Entity).into(),
+            Self::ApplicationLogRetention => Entity::has_many(super::application_log_retention::Entity).into(),
             Self::ApplicationProcessLogs => Entity::has_many(super::application_process_logs::Entity).into(),
+            Self::ApplicationProcessRuns => Entity::has_many(super::application_process_runs::Entity).into(),
             Self::ApplicationProcesses => Entity::has_many(super::application_processes::Entity).into(),
+            Self::ApplicationScopedSettings => Entity::has_many(super::application_scoped_settings::Entity).into(),
             Self::ApplicationSettings => Entity::has_many(super::application_settings::Entity).into(),
+            Self::ApplicationSettingsHistory => Entity::has_many(super::application_settings_history::Entity).into(),
             Self::ApplicationUserSettings => Entity::has_many(super::application_user_settings::Entity).into(),
         }
     }
@@ -90,24 +100,54 @@ impl Related<super::application_global_settings::Entity> for Entity {
     }
 }
 
+impl Related<super::application_keys::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationKeys.def()
+    }
+}
+
+impl Related<super::application_log_retention::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationLogRetention.def()
+    }
+}
+
 impl Related<super::application_process_logs::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ApplicationProcessLogs.def()
     }
 }
 
+impl Related<super::application_process_runs::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationProcessRuns.def()
+    }
+}
+
 impl Related<super::application_processes::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ApplicationProcesses.def()
     }
 }
 
+impl Related<super::application_scoped_settings::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationScopedSettings.def()
+    }
+}
+
 impl Related<super::application_settings::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ApplicationSettings.def()
     }
 }
 
+impl Related<super::application_settings_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApplicationSettingsHistory.def()
+    }
+}
+
 impl Related<super::application_user_settings::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ApplicationUserSettings.def()
@@ -3,8 +3,13 @@
 pub mod prelude;
 
 pub mod application_global_settings;
+pub mod application_keys;
+pub mod application_log_retention;
 pub mod application_process_logs;
+pub mod application_process_runs;
 pub mod application_processes;
+pub mod application_scoped_settings;
 pub mod application_settings;
+pub mod application_settings_history;
 pub mod application_user_settings;
 pub mod applications;
@@ -21,6 +21,9 @@ pub enum EnvVar {
     /// APPLICATION_HOST
     ApplicationHost,
 
+    /// SETTINGS_KEY
+    SettingsKey,
+
     /// This is a catch all. It has no direct map.
     Custom(&'static str),
 }
@@ -34,6 +37,7 @@ impl From<EnvVar> for &'static str {
             EnvVar::ApplicationSecret => "APPLICATION_SECRET",
             EnvVar::ApplicationHost => "APPLICATION_HOST",
             EnvVar::ApplicationName => "APPLICATION_NAME",
+            EnvVar::SettingsKey => "SETTINGS_KEY",
             EnvVar::Custom(key) => key, // just pass through the setting
         }
     }
@@ -48,6 +52,7 @@ impl From<&'static str> for EnvVar {
             "APPLICATION_SECRET" => EnvVar::DatabaseUrlSelf,
             "APPLICATION_HOST" => EnvVar::ApplicationHost,
             "APPLICATION_NAME" => EnvVar::ApplicationName,
+            "SETTINGS_KEY" => EnvVar::SettingsKey,
             data => EnvVar::Custom(data),
         }
     }
@@ -79,6 +84,7 @@ pub async fn save(new_vars: HashMap<&'static str, String>) -> anyhow::Result<()>
         EnvVar::ApplicationSecret,
         EnvVar::DatabaseUrlCore,
         EnvVar::DatabaseUrlSelf,
+        EnvVar::SettingsKey,
     ];
 
     let mut vars = HashMap::new();
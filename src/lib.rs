@@ -18,6 +18,9 @@ pub use uuid;
 
 pub use levelcrush_macros as proc_macros;
 
+// lets derives that refer to `::levelcrush` be used inside this crate as well
+extern crate self as levelcrush;
+
 pub mod alias;
 pub mod app;
 pub mod cache;
@@ -26,6 +29,7 @@ pub mod entities;
 pub mod macros;
 pub mod retry_lock;
 pub mod server;
+pub mod signing;
 pub mod task_pool;
 pub mod util;
 pub use sea_orm;
@@ -1,4 +1,5 @@
 pub use crate::proc_macros::*;
+pub use levelcrush_derive::SettingsBinding;
 
 #[macro_export]
 macro_rules! project_str (
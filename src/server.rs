@@ -11,6 +11,10 @@ use {
     axum_sessions::{SameSite, SessionLayer},
 };
 
+pub mod auth;
+pub mod logs;
+pub mod signature;
+
 #[derive(serde::Serialize, serde::Deserialize, Default, Debug, Clone)]
 pub struct PaginationData {
     pub total_results: u32,
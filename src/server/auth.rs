@@ -0,0 +1,252 @@
+use crate::app::{Application, ApplicationState};
+use crate::cache::{CacheDuration, CacheValue, MemoryCache};
+use crate::server::APIResponse;
+use crate::util::unix_timestamp;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, State},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// header that carries the application id (the application hash)
+pub const HEADER_APPLICATION_ID: &str = "x-application-id";
+
+/// header that carries the application secret or the secret of one of its keys
+pub const HEADER_APPLICATION_SECRET: &str = "x-application-secret";
+
+/// how long a successful authentication is cached unless configured otherwise.
+/// kept short since this is also how long a revoked key keeps working on other instances
+pub const DEFAULT_AUTH_CACHE: CacheDuration = CacheDuration::Custom(5);
+
+/// an authenticated application along with the revision of its credentials at the time
+#[derive(Clone)]
+struct CachedAuth<Extension>
+where
+    Extension: Clone + Send + Sync,
+{
+    application: Application<Extension>,
+    revision: u64,
+}
+
+/// Authenticates incoming request as a registered application.
+/// Successful lookups are cached so we are not hitting the database on every request.
+/// cached entries are dropped once the key expires or the credentials change through the same `ApplicationState`.
+///
+/// note: revoking a key, regenerating the secret or deleting the application only reaches the cache of this instance.
+/// other instances keep accepting the old credentials until their cached entry runs out, see `cache_duration`
+#[derive(Clone)]
+pub struct ApplicationAuth<Extension>
+where
+    Extension: Clone + Send + Sync,
+{
+    state: ApplicationState<Extension>,
+    cache: MemoryCache<CachedAuth<Extension>>,
+    duration: CacheDuration,
+}
+
+impl<Extension> ApplicationAuth<Extension>
+where
+    Extension: Clone + Send + Sync,
+{
+    pub fn new(state: &ApplicationState<Extension>) -> ApplicationAuth<Extension> {
+        ApplicationAuth {
+            state: state.clone(),
+            cache: MemoryCache::new(),
+            duration: DEFAULT_AUTH_CACHE,
+        }
+    }
+
+    /// how long an authenticated application stays cached before we check the database again.
+    /// credentials changed by another instance keep working in this one for up to this long
+    pub fn cache_duration(mut self, duration: CacheDuration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// looks up the application that matches the credentials, going through the cache first
+    pub async fn authenticate(&self, hash: &str, secret: &str) -> anyhow::Result<Option<Application<Extension>>> {
+        let cache_key = format!("{:x}", md5::compute(format!("{}||{}", hash, secret)));
+        // read before the lookup, so a change made while we are looking it up leaves the entry stale
+        let revision = self.state.credentials.get(hash);
+
+        if let Some(handle) = self.cache.access_handle(&cache_key).await {
+            let mut cached = handle.write().await;
+            if cached.revision == revision {
+                cached.application.touch_key();
+                return Ok(Some(cached.application.clone()));
+            }
+        }
+
+        let application = Application::get(hash, secret, &self.state).await?;
+        if let Some(application) = application.as_ref() {
+            // never outlive the key the application was loaded with
+            let mut duration = self.duration.i64();
+            if let Some(expires_at) = application.credentials_expire_at() {
+                duration = duration.min(expires_at - unix_timestamp());
+            }
+
+            if duration > 0 {
+                let cached = CachedAuth {
+                    application: application.clone(),
+                    revision,
+                };
+                let mut cache = self.cache.clone();
+                cache
+                    .write(cache_key, CacheValue::exact(cached, CacheDuration::Custom(duration)))
+                    .await;
+            }
+        }
+
+        Ok(application)
+    }
+
+    /// remove any expired entries from the cache
+    pub async fn prune(&self) {
+        let mut cache = self.cache.clone();
+        cache.prune().await;
+    }
+
+    /// pull the credentials out of the headers and authenticate them
+    pub async fn authenticate_headers(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Application<Extension>, ApplicationRejection> {
+        let (hash, secret) = credentials(headers).ok_or_else(ApplicationRejection::missing)?;
+        match self.authenticate(&hash, &secret).await {
+            Ok(Some(application)) => Ok(application),
+            Ok(None) => Err(ApplicationRejection::invalid()),
+            Err(err) => {
+                tracing::error!("Unable to authenticate application: {}", err);
+                Err(ApplicationRejection::internal())
+            }
+        }
+    }
+}
+
+/// Extract the application credentials from the request headers.
+/// Accepts either the `X-Application-Id` + `X-Application-Secret` pair or `Authorization: Bearer <id>:<secret>`
+pub fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let hash = headers.get(HEADER_APPLICATION_ID).and_then(|v| v.to_str().ok());
+    let secret = headers.get(HEADER_APPLICATION_SECRET).and_then(|v| v.to_str().ok());
+    if let (Some(hash), Some(secret)) = (hash, secret) {
+        return Some((hash.trim().to_string(), secret.trim().to_string()));
+    }
+
+    let authorization = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())?;
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let (hash, secret) = token.trim().split_once(':')?;
+    if hash.is_empty() || secret.is_empty() {
+        None
+    } else {
+        Some((hash.to_string(), secret.to_string()))
+    }
+}
+
+/// returned when a request could not be authenticated as an application
+#[derive(Debug, Clone)]
+pub struct ApplicationRejection {
+    status: StatusCode,
+    message: &'static str,
+}
+
+impl ApplicationRejection {
+    pub(crate) fn new(status: StatusCode, message: &'static str) -> ApplicationRejection {
+        ApplicationRejection { status, message }
+    }
+
+    fn missing() -> ApplicationRejection {
+        ApplicationRejection {
+            status: StatusCode::UNAUTHORIZED,
+            message: "Missing application credentials",
+        }
+    }
+
+    fn invalid() -> ApplicationRejection {
+        ApplicationRejection {
+            status: StatusCode::UNAUTHORIZED,
+            message: "Invalid application credentials",
+        }
+    }
+
+    fn internal() -> ApplicationRejection {
+        ApplicationRejection {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Unable to authenticate application",
+        }
+    }
+}
+
+impl IntoResponse for ApplicationRejection {
+    fn into_response(self) -> Response {
+        let mut api_response = APIResponse::<Option<String>>::new();
+        api_response.error("application", self.message);
+        api_response.complete();
+        (self.status, Json(api_response)).into_response()
+    }
+}
+
+#[async_trait]
+impl<S, Extension> FromRequestParts<S> for Application<Extension>
+where
+    S: Send + Sync,
+    Extension: Clone + Send + Sync + 'static,
+    ApplicationAuth<Extension>: FromRef<S>,
+{
+    type Rejection = ApplicationRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // the middleware has already done the work for us
+        if let Some(application) = parts.extensions.get::<Application<Extension>>() {
+            return Ok(application.clone());
+        }
+
+        let auth = ApplicationAuth::<Extension>::from_ref(state);
+        auth.authenticate_headers(&parts.headers).await
+    }
+}
+
+/// Middleware that rejects any request that does not authenticate as a registered application.
+/// The authenticated application is injected into the request so handlers can extract `Application<Extension>`
+///
+/// `router.layer(axum::middleware::from_fn_with_state(auth, require_application::<Extension, Body>))`
+pub async fn require_application<Extension, B>(
+    State(auth): State<ApplicationAuth<Extension>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApplicationRejection>
+where
+    Extension: Clone + Send + Sync + 'static,
+{
+    let application = auth.authenticate_headers(request.headers()).await?;
+    request.extensions_mut().insert(application);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    pub fn credentials_test() {
+        let mut headers = HeaderMap::new();
+        assert!(credentials(&headers).is_none());
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer abc:123"));
+        assert_eq!(credentials(&headers), Some(("abc".to_string(), "123".to_string())));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc:123"));
+        assert!(credentials(&headers).is_none());
+
+        headers.insert(HEADER_APPLICATION_ID, HeaderValue::from_static("def"));
+        headers.insert(HEADER_APPLICATION_SECRET, HeaderValue::from_static("456"));
+        assert_eq!(credentials(&headers), Some(("def".to_string(), "456".to_string())));
+    }
+}
@@ -0,0 +1,151 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRef, Query,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use futures::Stream;
+
+use crate::app::process::tail::{LogSubscription, LogTailFilter};
+use crate::app::Application;
+use crate::server::auth::{ApplicationAuth, ApplicationRejection};
+
+/// most recent entries a client can ask to replay on connect
+pub const MAX_REPLAY: usize = 1000;
+
+/// scope a key needs to tail the logs of its application
+pub const SCOPE_LOGS_READ: &str = "logs.read";
+
+/// query string accepted by both the sse and websocket routes
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug, Clone)]
+pub struct TailQuery {
+    /// name of the process
+    pub process: Option<String>,
+    /// only entries that are at least as severe as the level
+    pub level: Option<i8>,
+    pub sub_id: Option<String>,
+    /// recent entries to send before the live ones
+    #[serde(default)]
+    pub replay: usize,
+}
+
+/// live log routes for the authenticated application.
+/// `GET /logs/stream` streams entries as server sent events, `GET /logs/ws` upgrades to a websocket that sends each entry as json text.
+///
+/// entries are streamed from the log writer on the `ApplicationState` of the auth, so they have to be written through
+/// an application built from that same state. Keys need the `logs.read` scope, anything else gets a 403.
+///
+/// `routes.merge(server::logs::router::<_, Extension>())` on a state that provides `ApplicationAuth<Extension>`
+pub fn router<S, Extension>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Extension: Clone + Send + Sync + 'static,
+    ApplicationAuth<Extension>: FromRef<S>,
+{
+    Router::new()
+        .route("/logs/stream", get(stream::<Extension>))
+        .route("/logs/ws", get(websocket::<Extension>))
+}
+
+/// resolve the query into a subscription on the logs of the application
+async fn subscribe<Extension>(
+    application: &Application<Extension>,
+    query: TailQuery,
+) -> Result<LogSubscription, ApplicationRejection>
+where
+    Extension: Clone,
+{
+    if !application.has_scope(SCOPE_LOGS_READ) {
+        return Err(ApplicationRejection::new(
+            StatusCode::FORBIDDEN,
+            "Application credentials are missing the logs.read scope",
+        ));
+    }
+
+    let process = match query.process.as_deref() {
+        Some(name) => {
+            // app.process(...) would create the process if it did not exist
+            let processes = application.processes().await.map_err(|err| {
+                tracing::error!("Unable to list processes: {}", err);
+                ApplicationRejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Unable to list processes")
+            })?;
+            let process = processes
+                .into_iter()
+                .find(|process| process.name() == name)
+                .ok_or_else(|| ApplicationRejection::new(StatusCode::NOT_FOUND, "No process found"))?;
+            Some(process.id())
+        }
+        None => None,
+    };
+
+    let filter = LogTailFilter {
+        process,
+        level: query.level,
+        sub_id: query.sub_id,
+    };
+    Ok(application.tail_logs(filter, query.replay.min(MAX_REPLAY)))
+}
+
+async fn stream<Extension>(
+    application: Application<Extension>,
+    Query(query): Query<TailQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApplicationRejection>
+where
+    Extension: Clone + Send + Sync + 'static,
+{
+    let subscription = subscribe(&application, query).await?;
+    let events = futures::stream::unfold(subscription, |mut subscription| async move {
+        let log = subscription.recv().await?;
+        let event = Event::default().event("log").json_data(&log).unwrap_or_default();
+        Some((Ok(event), subscription))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
+
+async fn websocket<Extension>(
+    application: Application<Extension>,
+    Query(query): Query<TailQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response
+where
+    Extension: Clone + Send + Sync + 'static,
+{
+    match subscribe(&application, query).await {
+        Ok(subscription) => upgrade.on_upgrade(move |socket| forward(socket, subscription)),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// send entries until either side goes away. Anything the client sends is ignored
+async fn forward(mut socket: WebSocket, mut subscription: LogSubscription) {
+    loop {
+        tokio::select! {
+            log = subscription.recv() => match log {
+                Some(log) => {
+                    let text = serde_json::to_string(&log).unwrap_or_default();
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    let _ = socket.close().await;
+}
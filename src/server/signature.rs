@@ -0,0 +1,273 @@
+use crate::alias::UnixTimestamp;
+use crate::app::{Application, ApplicationState};
+use crate::cache::{CacheDuration, CacheValue, MemoryCache};
+use crate::server::auth::ApplicationRejection;
+use crate::signing::{self, HEADER_SIGNATURE, HEADER_SIGNATURE_APPLICATION, HEADER_SIGNATURE_TIMESTAMP};
+use crate::util::unix_timestamp;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{OriginalUri, State},
+    http::{header::CONTENT_LENGTH, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// largest request body that is buffered for verification unless configured otherwise
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Verifies that incoming request were signed by a registered application.
+/// Signatures that have already been seen inside the replay window are rejected
+#[derive(Clone)]
+pub struct SignatureVerifier<Extension>
+where
+    Extension: Clone + Send + Sync,
+{
+    state: ApplicationState<Extension>,
+    window: i64,
+    /// credentials of an application along with the revision they were loaded at
+    credentials: MemoryCache<(Vec<Application<Extension>>, u64)>,
+    seen: MemoryCache<UnixTimestamp>,
+    /// when expired signatures were last pruned, shared between clones
+    pruned_at: Arc<AtomicI64>,
+    body_limit: usize,
+}
+
+impl<Extension> SignatureVerifier<Extension>
+where
+    Extension: Clone + Send + Sync,
+{
+    pub fn new(state: &ApplicationState<Extension>) -> SignatureVerifier<Extension> {
+        SignatureVerifier {
+            state: state.clone(),
+            window: signing::DEFAULT_REPLAY_WINDOW,
+            credentials: MemoryCache::new(),
+            seen: MemoryCache::new(),
+            pruned_at: Arc::new(AtomicI64::new(unix_timestamp())),
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+
+    /// largest request body in bytes that `require_signature` accepts. Anything larger is rejected with 413
+    pub fn body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = bytes;
+        self
+    }
+
+    /// how many seconds (in either direction) a signed request is considered valid for
+    pub fn replay_window(mut self, seconds: i64) -> Self {
+        self.window = seconds;
+        self
+    }
+
+    /// verify the request and return the application that signed it
+    pub async fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<Application<Extension>, ApplicationRejection> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let (hash, timestamp, signature) = match (
+            header(HEADER_SIGNATURE_APPLICATION),
+            header(HEADER_SIGNATURE_TIMESTAMP),
+            header(HEADER_SIGNATURE),
+        ) {
+            (Some(hash), Some(timestamp), Some(signature)) => (hash, timestamp, signature),
+            _ => {
+                return Err(ApplicationRejection::new(
+                    StatusCode::UNAUTHORIZED,
+                    "Missing request signature",
+                ))
+            }
+        };
+
+        let timestamp = timestamp.parse::<UnixTimestamp>().unwrap_or_default();
+        if !signing::within_window(timestamp, self.window) {
+            return Err(ApplicationRejection::new(
+                StatusCode::UNAUTHORIZED,
+                "Request signature has expired",
+            ));
+        }
+
+        let credentials = self.credentials(hash).await.map_err(|err| {
+            tracing::error!("Unable to load application credentials: {}", err);
+            ApplicationRejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Unable to verify request signature")
+        })?;
+
+        let application = credentials
+            .into_iter()
+            .find(|application| signing::verify(application.secret(), method, path, body, timestamp, signature));
+
+        let application = application
+            .ok_or_else(|| ApplicationRejection::new(StatusCode::UNAUTHORIZED, "Invalid request signature"))?;
+
+        self.prune_seen().await;
+        if !self.claim(signature).await {
+            return Err(ApplicationRejection::new(
+                StatusCode::UNAUTHORIZED,
+                "Request signature has already been used",
+            ));
+        }
+
+        Ok(application)
+    }
+
+    /// remember the signature for the full window so it can't be replayed.
+    /// checked and inserted under one lock, so only one of several concurrent requests with the same signature wins.
+    /// only a digest of the signature is kept, the cache logs its keys when pruning
+    async fn claim(&self, signature: &str) -> bool {
+        let key = signing::body_digest(signature.as_bytes());
+        let mut seen = self.seen.lock_write().await;
+        if seen.get(&key).is_some_and(|value| value.look().is_some()) {
+            return false;
+        }
+
+        seen.insert(
+            key,
+            CacheValue::exact(unix_timestamp(), CacheDuration::Custom(self.window * 2)),
+        );
+        true
+    }
+
+    /// drop signatures that are past the replay window. Runs at most once per window
+    async fn prune_seen(&self) {
+        let now = unix_timestamp();
+        let pruned_at = self.pruned_at.load(Ordering::Relaxed);
+        if now - pruned_at < self.window.max(1) {
+            return;
+        }
+
+        // only one request does the pruning
+        if self
+            .pruned_at
+            .compare_exchange(pruned_at, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            let mut seen = self.seen.clone();
+            seen.prune().await;
+        }
+    }
+
+    /// all the credentials that can be used to sign a request for the application, going through the cache first
+    async fn credentials(&self, hash: &str) -> anyhow::Result<Vec<Application<Extension>>> {
+        let revision = self.state.credentials.get(hash);
+        if let Some((credentials, cached_revision)) = self.credentials.access(hash).await {
+            if cached_revision == revision {
+                return Ok(credentials);
+            }
+        }
+
+        let credentials = Application::credentials(hash, &self.state).await?;
+        if !credentials.is_empty() {
+            let mut cache = self.credentials.clone();
+            cache
+                .write(
+                    hash,
+                    CacheValue::exact((credentials.clone(), revision), CacheDuration::Minute),
+                )
+                .await;
+        }
+
+        Ok(credentials)
+    }
+}
+
+/// Middleware that rejects any request that was not signed by a registered application.
+/// The body is buffered so the digest can be checked, and the signing application is injected into the request
+///
+/// `router.layer(axum::middleware::from_fn_with_state(verifier, require_signature::<Extension>))`
+pub async fn require_signature<Extension>(
+    State(verifier): State<SignatureVerifier<Extension>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, ApplicationRejection>
+where
+    Extension: Clone + Send + Sync + 'static,
+{
+    let (parts, body) = request.into_parts();
+    let body = read_body(&parts.headers, body, verifier.body_limit).await?;
+
+    // nested routers only see the rest of the path, but clients sign the full path
+    let uri = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(&parts.uri, |original| &original.0);
+    let path = uri.path_and_query().map_or(uri.path(), |path| path.as_str());
+
+    let application = verifier
+        .verify(parts.method.as_str(), path, &parts.headers, &body)
+        .await?;
+
+    let mut request = Request::from_parts(parts, Body::from(body));
+    request.extensions_mut().insert(application);
+    Ok(next.run(request).await)
+}
+
+/// buffer the body, giving up as soon as it grows past the limit
+async fn read_body(headers: &HeaderMap, mut body: Body, limit: usize) -> Result<Bytes, ApplicationRejection> {
+    let too_large = || ApplicationRejection::new(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large");
+
+    let length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if length.is_some_and(|length| length > limit) {
+        return Err(too_large());
+    }
+
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk =
+            chunk.map_err(|_| ApplicationRejection::new(StatusCode::BAD_REQUEST, "Unable to read request body"))?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use sea_orm::DatabaseConnection;
+
+    #[tokio::test]
+    pub async fn signature_claim_test() {
+        let state = ApplicationState::new(DatabaseConnection::Disconnected, DatabaseConnection::Disconnected, ());
+        let verifier = SignatureVerifier::new(&state);
+
+        // concurrent requests with the same signature, only one gets through
+        let claims = futures::future::join_all((0..8).map(|_| verifier.claim("signature"))).await;
+        assert_eq!(claims.iter().filter(|claimed| **claimed).count(), 1);
+        assert!(verifier.claim("other").await);
+
+        // the signature itself is never stored
+        let seen = verifier.seen.lock_write().await;
+        assert!(!seen.contains_key("signature"));
+        assert!(seen.contains_key(&signing::body_digest(b"signature")));
+    }
+
+    #[tokio::test]
+    pub async fn read_body_test() {
+        let headers = HeaderMap::new();
+        let body = read_body(&headers, Body::from("abcd"), 4)
+            .await
+            .expect("Body was rejected");
+        assert_eq!(&body[..], b"abcd");
+
+        let rejection = read_body(&headers, Body::from("abcde"), 4)
+            .await
+            .expect_err("Body was accepted");
+        assert_eq!(rejection.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, "1000".parse().expect("Invalid header"));
+        assert!(read_body(&headers, Body::from("a"), 4).await.is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{alias::UnixTimestamp, util::unix_timestamp};
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// header that carries the application id (the application hash) of the signer
+pub const HEADER_SIGNATURE_APPLICATION: &str = "x-levelcrush-application";
+
+/// header that carries the unix timestamp the request was signed at
+pub const HEADER_SIGNATURE_TIMESTAMP: &str = "x-levelcrush-timestamp";
+
+/// header that carries the base64 encoded signature
+pub const HEADER_SIGNATURE: &str = "x-levelcrush-signature";
+
+/// how many seconds a signed request is considered valid for (in either direction)
+pub const DEFAULT_REPLAY_WINDOW: i64 = 300;
+
+/// hex encoded sha256 digest of the request body
+pub fn body_digest(body: &[u8]) -> String {
+    Sha256::digest(body).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// the string that is actually signed.
+/// path is expected to include the query string if there is one
+pub fn canonical(method: &str, path: &str, body: &[u8], timestamp: UnixTimestamp) -> String {
+    format!(
+        "{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path,
+        body_digest(body),
+        timestamp
+    )
+}
+
+/// generate a base64 encoded HMAC-SHA256 signature of the request with the supplied secret
+pub fn signature(secret: &str, method: &str, path: &str, body: &[u8], timestamp: UnixTimestamp) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(canonical(method, path, body, timestamp).as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// verify the signature against the request. The comparison is done in constant time
+pub fn verify(secret: &str, method: &str, path: &str, body: &[u8], timestamp: UnixTimestamp, signature: &str) -> bool {
+    let signature = match STANDARD.decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(canonical(method, path, body, timestamp).as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// checks if the timestamp falls within the replay window
+pub fn within_window(timestamp: UnixTimestamp, window: i64) -> bool {
+    (unix_timestamp() - timestamp).abs() <= window
+}
+
+/// sign an outgoing reqwest request with the supplied application credentials.
+/// streaming bodies cannot be signed since we need the full body to generate the digest
+pub fn sign_request(request: &mut reqwest::Request, application_id: &str, secret: &str) -> anyhow::Result<()> {
+    let body = match request.body() {
+        Some(body) => body
+            .as_bytes()
+            .ok_or_else(|| anyhow!("Unable to sign a streaming request body"))?
+            .to_vec(),
+        None => Vec::new(),
+    };
+
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let timestamp = unix_timestamp();
+    let signature = signature(secret, request.method().as_str(), &path, &body, timestamp);
+
+    let headers = request.headers_mut();
+    headers.insert(HEADER_SIGNATURE_APPLICATION, application_id.parse()?);
+    headers.insert(HEADER_SIGNATURE_TIMESTAMP, timestamp.to_string().parse()?);
+    headers.insert(HEADER_SIGNATURE, signature.parse()?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn signature_test() {
+        let timestamp = unix_timestamp();
+        let sig = signature("secret", "post", "/settings?page=1", b"{\"a\":1}", timestamp);
+
+        let check = |secret: &str, path: &str, body: &[u8], timestamp: UnixTimestamp| {
+            verify(secret, "POST", path, body, timestamp, &sig)
+        };
+
+        assert!(check("secret", "/settings?page=1", b"{\"a\":1}", timestamp));
+        assert!(!check("secret", "/settings?page=2", b"{\"a\":1}", timestamp));
+        assert!(!check("secret", "/settings?page=1", b"{\"a\":2}", timestamp));
+        assert!(!check("secret", "/settings?page=1", b"{\"a\":1}", timestamp + 1));
+        assert!(!check("other", "/settings?page=1", b"{\"a\":1}", timestamp));
+
+        assert!(within_window(timestamp, DEFAULT_REPLAY_WINDOW));
+        assert!(!within_window(
+            timestamp - DEFAULT_REPLAY_WINDOW - 10,
+            DEFAULT_REPLAY_WINDOW
+        ));
+    }
+
+    #[test]
+    pub fn sign_request_test() -> anyhow::Result<()> {
+        let client = reqwest::Client::new();
+        let mut request = client
+            .post("https://levelcrush.local/settings?page=1")
+            .body("hello world")
+            .build()?;
+
+        sign_request(&mut request, "app", "secret")?;
+
+        let headers = request.headers();
+        let timestamp = headers[HEADER_SIGNATURE_TIMESTAMP].to_str()?.parse::<i64>()?;
+        let sig = headers[HEADER_SIGNATURE].to_str()?;
+        assert_eq!(headers[HEADER_SIGNATURE_APPLICATION], "app");
+        assert!(verify(
+            "secret",
+            "POST",
+            "/settings?page=1",
+            b"hello world",
+            timestamp,
+            sig
+        ));
+
+        Ok(())
+    }
+}